@@ -0,0 +1,13 @@
+// Draws a chunk layer from its tileset image, shifted to the current
+// animation frame (see TilemapMaterial)
+
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(1) @binding(0) var<uniform> frame_offset: f32;
+@group(1) @binding(1) var tileset_texture: texture_2d<f32>;
+@group(1) @binding(2) var tileset_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(tileset_texture, tileset_sampler, mesh.uv + vec2<f32>(0.0, frame_offset));
+}
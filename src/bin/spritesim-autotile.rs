@@ -0,0 +1,123 @@
+//! Contact sheet of an autotile rule set.
+//!
+//! Every one of the 256 possible neighbourhoods is resolved through the rules
+//! and drawn as the game would, for humans to review (the rules themselves are
+//! checked by the tests of `autotile`).
+
+use std::{path::PathBuf, process::exit};
+
+use bevy_spritesim::{
+    cli::get_setting_value,
+    mapgen::{
+        autotile::{
+            build_neighbourhood, get_tiles_to_display, AutotileRules, Neighbour, TILESET_WIDTH,
+        },
+        Kind, Layer, TerrainKind, SPRITE_SIZE,
+    },
+    tilesets::get_asset_path,
+};
+use image::{imageops, RgbaImage};
+
+/// Room (in pixels) between two cases of the contact sheet
+const CASE_PADDING: u32 = 4;
+/// Cases are laid out on a 16x16 grid, by increasing neighbours bitmask
+const CASES_PER_ROW: u32 = 16;
+
+/// The Kind whose tile is being autotiled
+const FOREGROUND: Kind = Kind::TKind(TerrainKind::Desert);
+/// The Kind of the neighbours that are not of the foreground Kind
+const BACKGROUND: Kind = Kind::TKind(TerrainKind::Plain);
+
+/// Load a tileset image from the assets folder
+fn load_tileset(path: &str) -> RgbaImage {
+    let full_path = get_asset_path(path);
+    return image::open(&full_path)
+        .unwrap_or_else(|error| panic!("Cannot load «{}»: {}", full_path.display(), error))
+        .to_rgba8();
+}
+
+/// Draw the sprite at the given index of a tileset (first animation frame)
+fn draw_sprite(sheet: &mut RgbaImage, tileset: &RgbaImage, index: usize, x: u32, y: u32) {
+    let size = SPRITE_SIZE as u32;
+    let sprite = imageops::crop_imm(
+        tileset,
+        (index % TILESET_WIDTH) as u32 * size,
+        (index / TILESET_WIDTH) as u32 * size,
+        size,
+        size,
+    )
+    .to_image();
+    imageops::overlay(sheet, &sprite, x as i64, y as i64);
+}
+
+fn main() {
+    let rules = match get_setting_value("--rules", "SPRITESIM_AUTOTILE_RULES") {
+        Some(path) => AutotileRules::load(PathBuf::from(path)).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            exit(2);
+        }),
+        None => AutotileRules::default(),
+    };
+    let output = get_setting_value("--output", "SPRITESIM_AUTOTILE_OUTPUT")
+        .unwrap_or("autotile_sheet.png".to_string());
+    let foreground_tileset = load_tileset(
+        &get_setting_value("--foreground", "SPRITESIM_AUTOTILE_FOREGROUND")
+            .unwrap_or("sprites/terrain/desert.png".to_string()),
+    );
+    let background_tileset = load_tileset(
+        &get_setting_value("--background", "SPRITESIM_AUTOTILE_BACKGROUND")
+            .unwrap_or("sprites/terrain/plain.png".to_string()),
+    );
+
+    // The neighbours are drawn as full tiles
+    let full_index = rules.get_full_index();
+    let size = SPRITE_SIZE as u32;
+    let case_size = 3 * size + CASE_PADDING;
+    let mut sheet = RgbaImage::new(
+        CASES_PER_ROW * case_size + CASE_PADDING,
+        (256 / CASES_PER_ROW) * case_size + CASE_PADDING,
+    );
+
+    for neighbours in 0..=u8::MAX {
+        let map = build_neighbourhood(neighbours, FOREGROUND, BACKGROUND);
+        let center = map.get(&(0, 0)).unwrap();
+        let (index, background) =
+            get_tiles_to_display(center, &map, &(0, 0), Layer::Terrain, Some(&rules));
+
+        // Contact sheet: the neighbours are drawn as full tiles, and the
+        // center as the game would draw it
+        let x = CASE_PADDING + (neighbours as u32 % CASES_PER_ROW) * case_size;
+        let y = CASE_PADDING + (neighbours as u32 / CASES_PER_ROW) * case_size;
+        for neighbour in Neighbour::ALL {
+            let offset = neighbour.get_offset();
+            let tileset = if neighbours & neighbour.get_bit() != 0 {
+                &foreground_tileset
+            } else {
+                &background_tileset
+            };
+            // Image rows go downward while map rows go upward
+            draw_sprite(
+                &mut sheet,
+                tileset,
+                full_index,
+                x + ((1 + offset.0) as u32) * size,
+                y + ((1 - offset.1) as u32) * size,
+            );
+        }
+        if let Some(kind) = background {
+            let tileset = if kind == FOREGROUND {
+                &foreground_tileset
+            } else {
+                &background_tileset
+            };
+            // Like in game, backgrounds are full sprites
+            draw_sprite(&mut sheet, tileset, full_index, x + size, y + size);
+        }
+        draw_sprite(&mut sheet, &foreground_tileset, index, x + size, y + size);
+    }
+
+    sheet
+        .save(&output)
+        .unwrap_or_else(|error| panic!("Cannot save «{}»: {}", output, error));
+    println!("Contact sheet saved to «{}»", output);
+}
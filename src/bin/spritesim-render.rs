@@ -0,0 +1,222 @@
+//! Render a generated map to a PNG, without a window nor a GPU.
+//!
+//! The map is built from the same seed (`--seed`) and preset (`--preset`) as
+//! the game would, then every tile is composited on the CPU from the first
+//! animation frame of its tilesets, picking sprites with the same autotile
+//! rules as the game. Renders of a given seed and preset are
+//! identical, so they can be diffed in CI when tweaking the generator.
+//!
+//! With `--mode overview`, every tile is a single pixel instead, which is
+//! enough to eyeball the shape of many maps: `--count` renders that many maps,
+//! from consecutive seeds. `{seed}` in the `--output` path is replaced by the
+//! seed of each map.
+
+use std::process::exit;
+
+use bevy::utils::HashMap;
+use bevy_spritesim::{
+    cli::get_setting_value,
+    mapgen::{
+        autotile::{get_tiles_to_display, AutotileRules, TILESET_WIDTH},
+        build_map,
+        config::MapGenConfig,
+        get_kind_of_tile_layer, Kind, Layer, Map, MapSeed, SPRITE_SIZE,
+    },
+    overview::render_overview,
+    tilesets::{get_asset_path, load_tileset_rules, TILESETS},
+};
+use image::{imageops, RgbaImage};
+
+/// How maps are drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RenderMode {
+    /// Every tile drawn from its tilesets, like in game
+    Tiles,
+    /// One pixel per tile (see `overview`)
+    Overview,
+}
+
+/// Where a Kind is drawn from
+struct Tileset<'a> {
+    image: &'a RgbaImage,
+    /// Position (in pixels) of the first sprite inside the image
+    offset: (u32, u32),
+    rules: Option<AutotileRules>,
+}
+
+/// A sprite to draw on a tile, resolved through the autotile rules
+struct TileSprite {
+    coordinates: (i32, i32),
+    layer: Layer,
+    kind: Kind,
+    index: usize,
+    /// Kind of the composite background to draw under the sprite, if any
+    background: Option<Kind>,
+}
+
+/// Load an image from the assets folder
+fn load_image(path: &str) -> RgbaImage {
+    let full_path = get_asset_path(path);
+    return image::open(&full_path)
+        .unwrap_or_else(|error| panic!("Cannot load «{}»: {}", full_path.display(), error))
+        .to_rgba8();
+}
+
+/// Draw the sprite at the given index of a tileset (first animation frame)
+/// over the tile at the given coordinates
+fn draw_sprite(
+    render: &mut RgbaImage,
+    tileset: &Tileset,
+    index: usize,
+    coordinates: &(i32, i32),
+    map_height: i32,
+) {
+    let size = SPRITE_SIZE as u32;
+    let sprite = imageops::crop_imm(
+        tileset.image,
+        tileset.offset.0 + (index % TILESET_WIDTH) as u32 * size,
+        tileset.offset.1 + (index / TILESET_WIDTH) as u32 * size,
+        size,
+        size,
+    )
+    .to_image();
+    // Image rows go downward while map rows go upward
+    imageops::overlay(
+        render,
+        &sprite,
+        (coordinates.0 as u32 * size) as i64,
+        ((map_height - coordinates.1) as u32 * size) as i64,
+    );
+}
+
+/// Composite the whole map, in the same order the game draws it: composite
+/// backgrounds first, then every layer from bottom to top
+fn render_map(map: &Map, tilesets: &HashMap<Kind, Tileset>, width: i32, height: i32) -> RgbaImage {
+    let size = SPRITE_SIZE as u32;
+    let mut render = RgbaImage::new((width + 1) as u32 * size, (height + 1) as u32 * size);
+
+    // Sprites (and their backgrounds) are resolved once, then drawn pass by pass
+    let mut sprites: Vec<TileSprite> = Vec::new();
+    for w in 0..=width {
+        for h in 0..=height {
+            let Some(tile) = map.get(&(w, h)) else {
+                continue;
+            };
+            for layer in Layer::ALL {
+                let Some(kind) = get_kind_of_tile_layer(tile, &layer) else {
+                    continue;
+                };
+                let rules = tilesets
+                    .get(&kind)
+                    .and_then(|tileset| tileset.rules.as_ref());
+                let (index, background) = get_tiles_to_display(tile, map, &(w, h), layer, rules);
+                sprites.push(TileSprite {
+                    coordinates: (w, h),
+                    layer,
+                    kind,
+                    index,
+                    background,
+                });
+            }
+        }
+    }
+
+    for sprite in &sprites {
+        if let Some(tileset) = sprite.background.and_then(|kind| tilesets.get(&kind)) {
+            let index = tileset
+                .rules
+                .as_ref()
+                .map_or(0, |rules| rules.get_full_index());
+            draw_sprite(&mut render, tileset, index, &sprite.coordinates, height);
+        }
+    }
+    for layer in Layer::ALL {
+        for sprite in sprites.iter().filter(|sprite| sprite.layer == layer) {
+            if let Some(tileset) = tilesets.get(&sprite.kind) {
+                draw_sprite(
+                    &mut render,
+                    tileset,
+                    sprite.index,
+                    &sprite.coordinates,
+                    height,
+                );
+            }
+        }
+    }
+
+    return render;
+}
+
+fn main() {
+    let first_seed = MapSeed::from_env();
+    let config = MapGenConfig::from_env().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(2);
+    });
+    let mode = match get_setting_value("--mode", "SPRITESIM_RENDER_MODE").as_deref() {
+        None | Some("tiles") => RenderMode::Tiles,
+        Some("overview") => RenderMode::Overview,
+        Some(other) => {
+            eprintln!(
+                "Unknown render mode «{}» (expected «tiles» or «overview»)",
+                other
+            );
+            exit(2);
+        }
+    };
+    let count: u64 = match get_setting_value("--count", "SPRITESIM_RENDER_COUNT") {
+        Some(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number of maps «{}»", value);
+            exit(2);
+        }),
+        None => 1,
+    };
+    let output = get_setting_value("--output", "SPRITESIM_RENDER_OUTPUT").unwrap_or(
+        match mode {
+            RenderMode::Tiles => "map_{seed}.png",
+            RenderMode::Overview => "overview_{seed}.png",
+        }
+        .to_string(),
+    );
+
+    // Images are shared by several Kinds (like specials), so they are only
+    // loaded once (and not at all for overviews)
+    let mut images: HashMap<&str, RgbaImage> = HashMap::new();
+    if mode == RenderMode::Tiles {
+        for (_, path, _, _) in TILESETS {
+            images.entry(path).or_insert_with(|| load_image(path));
+        }
+    }
+    let tilesets: HashMap<Kind, Tileset> = TILESETS
+        .iter()
+        .filter(|(_, path, _, _)| images.contains_key(path))
+        .map(|(kind, path, offset, rules_path)| {
+            let rules = rules_path.map(|rules_path| {
+                load_tileset_rules(rules_path).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    exit(2);
+                })
+            });
+            let tileset = Tileset {
+                image: &images[path],
+                offset: (offset.x as u32, offset.y as u32),
+                rules,
+            };
+            (*kind, tileset)
+        })
+        .collect();
+
+    for seed in (0..count).map(|offset| first_seed.0.wrapping_add(offset)) {
+        let map = build_map(&MapSeed(seed), &config);
+        let render = match mode {
+            RenderMode::Tiles => render_map(&map, &tilesets, config.width, config.height),
+            RenderMode::Overview => render_overview(&map, config.width, config.height),
+        };
+
+        let path = output.replace("{seed}", &seed.to_string());
+        render
+            .save(&path)
+            .unwrap_or_else(|error| panic!("Cannot save «{}»: {}", path, error));
+        println!("Map with seed {} rendered to «{}»", seed, path);
+    }
+}
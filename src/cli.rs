@@ -0,0 +1,18 @@
+/// Retrieve the value of a setting from the command line (either `--name value`
+/// or `--name=value`), then from the given environment variable.
+///
+/// The command line always takes precedence over the environment.
+pub fn get_setting_value(arg: &str, env_var: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let from_args = args.iter().enumerate().find_map(|(i, current)| {
+        if current == arg {
+            args.get(i + 1).cloned()
+        } else {
+            current
+                .strip_prefix(&format!("{}=", arg))
+                .map(|value| value.to_string())
+        }
+    });
+
+    return from_args.or_else(|| std::env::var(env_var).ok());
+}
@@ -0,0 +1,8 @@
+//! Everything that does not need a window: world generation and the helpers it
+//! relies on. The game itself (rendering, camera, etc.) lives in the binary and
+//! builds on top of it.
+
+pub mod cli;
+pub mod mapgen;
+pub mod overview;
+pub mod tilesets;
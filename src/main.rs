@@ -1,13 +1,20 @@
 use bevy::{prelude::*, window::*};
+use bevy_spritesim::mapgen::{config::MapGenConfig, MapSeed};
 use plugins::camera::CameraPlugin;
 use plugins::constants::{WINDOW_PHYSICAL_HEIGHT, WINDOW_PHYSICAL_WIDTH, WINDOW_SCALE_FACTOR};
+use plugins::editor::EditorPlugin;
 use plugins::map::MapPlugin;
+use plugins::minimap::MinimapPlugin;
+use plugins::picking::PickingPlugin;
+use plugins::unit::UnitPlugin;
 
 mod plugins;
 
 /// There we go !
 fn main() {
     App::new()
+        .insert_resource(MapSeed::from_env())
+        .insert_resource(MapGenConfig::from_env().unwrap_or_else(|error| panic!("{}", error)))
         .add_plugins((
             DefaultPlugins
                 .set(WindowPlugin {
@@ -28,6 +35,10 @@ fn main() {
                 })
                 .set(ImagePlugin::default_nearest()),
             MapPlugin,
+            UnitPlugin,
+            PickingPlugin,
+            EditorPlugin,
+            MinimapPlugin,
             CameraPlugin,
         ))
         .run();
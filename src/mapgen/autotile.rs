@@ -0,0 +1,395 @@
+use std::{fmt, fs, path::PathBuf};
+
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    climate::Climate, get_kind_of_tile_layer, get_layer_from_kind, Kind, Layer, Map, Tile,
+    TileLayers, SPRITE_SIZE,
+};
+
+/// How many sprites a row of the first animation frame of a tileset holds
+pub const TILESET_WIDTH: usize = 7;
+/// How many rows of sprites the first animation frame of a tileset holds
+pub const TILESET_HEIGHT: usize = 7;
+
+/// Rules of the 7x7 «blob» tilesets every terrain shipped with the game uses
+const DEFAULT_RULES: &str = include_str!("../../assets/autotile/blob_7x7.ron");
+
+/// The 8 tiles surrounding a tile:
+///
+/// top_left    | top      | top_right
+/// left        | OUR TILE | right
+/// bottom_left | bottom   | bottom_right
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Neighbour {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Neighbour {
+    /// Every neighbour, in the order they are read in patterns (and in bitmasks,
+    /// from the lowest bit to the highest)
+    pub const ALL: [Neighbour; 8] = [
+        Neighbour::TopLeft,
+        Neighbour::Top,
+        Neighbour::TopRight,
+        Neighbour::Left,
+        Neighbour::Right,
+        Neighbour::BottomLeft,
+        Neighbour::Bottom,
+        Neighbour::BottomRight,
+    ];
+
+    /// Offset of the neighbour coordinates relative to our tile
+    pub fn get_offset(&self) -> (i32, i32) {
+        return match self {
+            Neighbour::TopLeft => (-1, 1),
+            Neighbour::Top => (0, 1),
+            Neighbour::TopRight => (1, 1),
+            Neighbour::Left => (-1, 0),
+            Neighbour::Right => (1, 0),
+            Neighbour::BottomLeft => (-1, -1),
+            Neighbour::Bottom => (0, -1),
+            Neighbour::BottomRight => (1, -1),
+        };
+    }
+
+    /// Bit of the neighbour in a neighbours bitmask
+    pub fn get_bit(&self) -> u8 {
+        return 1 << Neighbour::ALL.iter().position(|n| n == self).unwrap();
+    }
+}
+
+/// Which neighbours must (or must not) be of the same Kind as our tile.
+///
+/// It is written as 8 characters (see `Neighbour::ALL` for their order), «1»
+/// for a neighbour of the same Kind, «0» for another Kind and «_» for any,
+/// with optional spaces for readability (e.g. "_0_ 01 _11").
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NeighbourPattern {
+    /// Neighbours that are taken into account
+    mask: u8,
+    /// Expected value of these neighbours
+    value: u8,
+}
+
+impl NeighbourPattern {
+    /// Whether a neighbours bitmask (a bit being set when the neighbour is
+    /// of the same Kind) matches the pattern
+    pub fn matches(&self, neighbours: u8) -> bool {
+        return neighbours & self.mask == self.value;
+    }
+}
+
+impl TryFrom<String> for NeighbourPattern {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        let characters: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+        if characters.len() != Neighbour::ALL.len() {
+            return Err(format!(
+                "pattern «{}» must describe exactly {} neighbours",
+                pattern,
+                Neighbour::ALL.len()
+            ));
+        }
+
+        let mut mask = 0;
+        let mut value = 0;
+        for (bit, character) in characters.iter().enumerate() {
+            match character {
+                '1' => {
+                    mask |= 1 << bit;
+                    value |= 1 << bit;
+                }
+                '0' => mask |= 1 << bit,
+                '_' => {}
+                other => {
+                    return Err(format!(
+                        "pattern «{}» contains «{}», expected «0», «1» or «_»",
+                        pattern, other
+                    ))
+                }
+            }
+        }
+        return Ok(NeighbourPattern { mask, value });
+    }
+}
+
+impl From<NeighbourPattern> for String {
+    fn from(pattern: NeighbourPattern) -> Self {
+        let characters: String = (0..Neighbour::ALL.len())
+            .map(
+                |bit| match (pattern.mask >> bit & 1, pattern.value >> bit & 1) {
+                    (0, _) => '_',
+                    (_, 1) => '1',
+                    _ => '0',
+                },
+            )
+            .collect();
+        return format!(
+            "{} {} {}",
+            &characters[0..3],
+            &characters[3..5],
+            &characters[5..8]
+        );
+    }
+}
+
+/// Which sprite to use when the neighbours of a tile match a pattern
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutotileRule {
+    pub pattern: NeighbourPattern,
+    /// Index of the sprite in the first animation frame of the tileset
+    pub index: usize,
+    /// The neighbour whose Kind is drawn under the sprite, if it is a partial one
+    pub background: Option<Neighbour>,
+}
+
+/// How to pick sprites in a given tileset layout.
+///
+/// Rules are tried in order, so more specific rules must come first.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutotileRules {
+    pub rules: Vec<AutotileRule>,
+    /// Other Kinds (on the same layer) that tiles connect to as if they were
+    /// of their own Kind, like rivers flowing into the sea
+    #[serde(default)]
+    pub connects_to: Vec<Kind>,
+}
+
+impl Default for AutotileRules {
+    /// Rules of the 7x7 «blob» layout (47 tiles + variations)
+    fn default() -> Self {
+        return ron::from_str(DEFAULT_RULES).expect("Default autotile rules must be valid");
+    }
+}
+
+/// Everything that can go wrong when loading AutotileRules
+#[derive(Debug)]
+pub enum AutotileRulesError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+}
+
+impl fmt::Display for AutotileRulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            AutotileRulesError::Io(path, error) => {
+                write!(f, "Cannot read «{}»: {}", path.display(), error)
+            }
+            AutotileRulesError::Parse(path, error) => {
+                write!(f, "Cannot parse «{}»: {}", path.display(), error)
+            }
+        };
+    }
+}
+
+impl std::error::Error for AutotileRulesError {}
+
+impl AutotileRules {
+    /// Read rules from a RON file
+    pub fn load(path: PathBuf) -> Result<Self, AutotileRulesError> {
+        let content = fs::read_to_string(&path)
+            .map_err(|error| AutotileRulesError::Io(path.clone(), error))?;
+        return ron::from_str(&content).map_err(|error| AutotileRulesError::Parse(path, error));
+    }
+
+    /// Retrieve the first rule matching a neighbours bitmask
+    pub fn resolve(&self, neighbours: u8) -> Option<&AutotileRule> {
+        return self
+            .rules
+            .iter()
+            .find(|rule| rule.pattern.matches(neighbours));
+    }
+
+    /// Index of the sprite of a tile surrounded by its own Kind, which is the
+    /// one drawn when the tileset serves as a composite background (the
+    /// other sprites may be partial ones as well)
+    pub fn get_full_index(&self) -> usize {
+        return self.resolve(u8::MAX).map_or(0, |rule| rule.index);
+    }
+}
+
+/// Retrieve the adequate tileset indices to properly display a tile.
+///
+/// Indeed, tiles can either be one in the center of a patch (hence the tileable
+/// center tile will be used), or on the edge (maybe even in a corner), so a proper
+/// algorithmic pass must done to ensure the proper tile is used. This pass is
+/// driven by the autotile rules of the tileset: tilesets without rules (like
+/// most specials) always use their first sprite.
+///
+/// Additionnaly if a «partial» tile (like a corner) is used, we have to add
+/// an underlying tile to serve as background so for instance a beach is composed of
+/// a plain (its shore) and the ocean (its beach) over it.
+pub fn get_tiles_to_display(
+    tile: &Tile,
+    map: &Map,
+    coordinates: &(i32, i32),
+    layer: Layer,
+    rules: Option<&AutotileRules>,
+) -> (usize, Option<Kind>) {
+    let Some(rules) = rules else {
+        return (0, None);
+    };
+
+    let kind = get_kind_of_tile_layer(tile, &layer);
+
+    // Tiles on the map edges consider the outside of the map as made of
+    // themselves
+    let get_neighbour_kind = |neighbour: &Neighbour| {
+        let offset = neighbour.get_offset();
+        return get_kind_of_tile_layer(
+            map.get(&(coordinates.0 + offset.0, coordinates.1 + offset.1))
+                .unwrap_or(tile),
+            &layer,
+        );
+    };
+
+    // The rules rely on a truth table which determines a tileset index to use
+    // based on the ones surrounding the current tile, hence the neighbours
+    // bitmask where a bit is set for every neighbour of the same Kind (or of
+    // a Kind it connects to).
+    let neighbours = Neighbour::ALL
+        .iter()
+        .filter(|neighbour| {
+            let neighbour_kind = get_neighbour_kind(neighbour);
+            return neighbour_kind == kind
+                || neighbour_kind.is_some_and(|other| rules.connects_to.contains(&other));
+        })
+        .fold(0, |mask, neighbour| mask | neighbour.get_bit());
+
+    // A second value is returned, which is either None (for regular «full» tiles),
+    // or Some(kind) which is the "background" tile on top of which a partial tile
+    // will be applied (think an ocean shore on top of a plain to make a beach).
+    return match rules.resolve(neighbours) {
+        Some(rule) => (
+            rule.index,
+            rule.background
+                .and_then(|neighbour| get_neighbour_kind(&neighbour)),
+        ),
+        None => (0, None),
+    };
+}
+
+/// Build a 3x3 map around (0, 0) whose center is of the foreground Kind, and
+/// whose neighbours are of the foreground Kind when their bit is set in the
+/// neighbours bitmask (and of the background Kind otherwise), to check or
+/// review how a given neighbourhood is autotiled
+pub fn build_neighbourhood(neighbours: u8, foreground: Kind, background: Kind) -> Map {
+    let mut map: Map = HashMap::new();
+    let mut insert = |coordinates: (i32, i32), kind: Kind| {
+        let mut layers = TileLayers::new();
+        layers.insert(get_layer_from_kind(&kind), kind);
+        map.insert(
+            coordinates,
+            Tile {
+                layers,
+                real_coordinates: (
+                    coordinates.0 as f32 * SPRITE_SIZE,
+                    coordinates.1 as f32 * SPRITE_SIZE,
+                ),
+                climate: Climate::default(),
+                water_body: None,
+            },
+        );
+    };
+
+    insert((0, 0), foreground);
+    for neighbour in Neighbour::ALL {
+        insert(
+            neighbour.get_offset(),
+            if neighbours & neighbour.get_bit() != 0 {
+                foreground
+            } else {
+                background
+            },
+        );
+    }
+    return map;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::TerrainKind;
+
+    const FOREGROUND: Kind = Kind::TKind(TerrainKind::Desert);
+    const BACKGROUND: Kind = Kind::TKind(TerrainKind::Plain);
+
+    /// Resolve every one of the 256 possible neighbourhoods through a shipped
+    /// rule file, and ensure each of them gives a sprite of the tileset, with
+    /// partial sprites drawn over a background of another Kind
+    fn check_every_case(file: &str) {
+        let rules = AutotileRules::load(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join("autotile")
+                .join(file),
+        )
+        .unwrap();
+
+        for neighbours in 0..=u8::MAX {
+            let map = build_neighbourhood(neighbours, FOREGROUND, BACKGROUND);
+            let center = &map[&(0, 0)];
+            let rule = rules
+                .resolve(neighbours)
+                .unwrap_or_else(|| panic!("{}: no rule matches {:08b}", file, neighbours));
+
+            let (index, background) =
+                get_tiles_to_display(center, &map, &(0, 0), Layer::Terrain, Some(&rules));
+            assert!(
+                index < TILESET_WIDTH * TILESET_HEIGHT,
+                "{}: sprite {} of {:08b} is outside of the tileset",
+                file,
+                index,
+                neighbours
+            );
+            if rule.background.is_some() {
+                assert!(
+                    background.is_some_and(|kind| kind != FOREGROUND),
+                    "{}: partial sprite {} of {:08b} is drawn over {:?}",
+                    file,
+                    index,
+                    neighbours,
+                    background
+                );
+            }
+            if neighbours == u8::MAX {
+                assert_eq!(
+                    background, None,
+                    "{}: the full sprite {} has a background",
+                    file, index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn blob_rules_cover_every_case() {
+        check_every_case("blob_7x7.ron");
+    }
+
+    #[test]
+    fn cardinal_rules_cover_every_case() {
+        check_every_case("cardinal_16.ron");
+    }
+
+    #[test]
+    fn ocean_rules_cover_every_case() {
+        check_every_case("ocean_7x7.ron");
+    }
+
+    #[test]
+    fn river_rules_cover_every_case() {
+        check_every_case("river_16.ron");
+    }
+}
@@ -0,0 +1,98 @@
+//! Which terrain a climate gives, along the lines of a Whittaker diagram:
+//! temperature picks a climate zone, then moisture picks a biome inside it.
+//!
+//! | Temperature | Dry       | Moist   | Wet    |
+//! |-------------|-----------|---------|--------|
+//! | freezing    | Snow      | Snow    | Snow   |
+//! | cold        | Tundra    | Tundra  | Tundra |
+//! | temperate   | Grassland | Plain   | Swamp  |
+//! | hot         | Desert    | Savanna | Jungle |
+//!
+//! Both temperature and moisture are smooth (see `climate`), so biomes form
+//! coherent regions.
+
+use super::{climate::Climate, config::BiomesConfig, TerrainKind};
+
+/// The terrain of a tile, given its climate
+pub fn get_biome(climate: &Climate, config: &BiomesConfig) -> TerrainKind {
+    let is_dry = climate.moisture < config.dry;
+    let is_wet = climate.moisture >= config.wet;
+    return match climate.temperature {
+        t if t < config.freezing => TerrainKind::Snow,
+        t if t < config.cold => TerrainKind::Tundra,
+        t if t < config.hot => match (is_dry, is_wet) {
+            (true, _) => TerrainKind::Grassland,
+            (_, true) => TerrainKind::Swamp,
+            _ => TerrainKind::Plain,
+        },
+        _ => match (is_dry, is_wet) {
+            (true, _) => TerrainKind::Desert,
+            (_, true) => TerrainKind::Jungle,
+            _ => TerrainKind::Savanna,
+        },
+    };
+}
+
+/// Whether forests grow on a terrain (taiga, woods and rainforests)
+pub fn can_grow_forest(terrain: &TerrainKind) -> bool {
+    return matches!(
+        terrain,
+        TerrainKind::Plain | TerrainKind::Grassland | TerrainKind::Tundra | TerrainKind::Jungle
+    );
+}
+
+/// Whether corn grows on a terrain
+pub fn can_grow_corn(terrain: &TerrainKind) -> bool {
+    return matches!(
+        terrain,
+        TerrainKind::Plain | TerrainKind::Grassland | TerrainKind::Savanna
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biomes_change_at_the_thresholds() {
+        let config = BiomesConfig {
+            freezing: 0.1,
+            cold: 0.3,
+            hot: 0.7,
+            dry: 0.4,
+            wet: 0.6,
+        };
+        // Temperature, moisture and the biome they give
+        let cases = [
+            (0., 0.5, TerrainKind::Snow),
+            (0.09, 1., TerrainKind::Snow),
+            (0.1, 0., TerrainKind::Tundra),
+            (0.29, 1., TerrainKind::Tundra),
+            (0.3, 0.39, TerrainKind::Grassland),
+            (0.3, 0.4, TerrainKind::Plain),
+            (0.69, 0.59, TerrainKind::Plain),
+            (0.5, 0.6, TerrainKind::Swamp),
+            (0.69, 1., TerrainKind::Swamp),
+            (0.7, 0., TerrainKind::Desert),
+            (1., 0.39, TerrainKind::Desert),
+            (0.7, 0.4, TerrainKind::Savanna),
+            (1., 0.59, TerrainKind::Savanna),
+            (0.7, 0.6, TerrainKind::Jungle),
+            (1., 1., TerrainKind::Jungle),
+        ];
+        for (temperature, moisture, biome) in cases {
+            let climate = Climate {
+                elevation: 0.,
+                moisture,
+                temperature,
+            };
+            assert_eq!(
+                get_biome(&climate, &config),
+                biome,
+                "temperature {}, moisture {}",
+                temperature,
+                moisture
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! The continuous values behind the Kinds of every tile.
+//!
+//! Elevation comes from the height noise map the terrain is thresholded from,
+//! temperature from the latitude (warm around the middle of the map, cold
+//! towards its top and bottom) and moisture from a noise map of its own.
+//! Passes that need more than the Kinds of a tile (like rivers flowing
+//! downhill) read them from its Climate.
+
+use bevy::{
+    math::{vec2, Vec2},
+    utils::HashMap,
+};
+use noisy_bevy::fbm_simplex_2d_seeded;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{config::MapGenConfig, draw_noise_seed, MapSeed};
+
+/// Octaves of the moisture and temperature noise maps, which only need to be
+/// smooth
+const CLIMATE_OCTAVES: usize = 4;
+const CLIMATE_LACUNARITY: f32 = 2.;
+const CLIMATE_GAIN: f32 = 0.5;
+
+/// Continuous values of a tile
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Climate {
+    /// Height in the noise map, comparable to the height thresholds of the
+    /// configuration (below the plain threshold is under water)
+    pub elevation: f32,
+    /// From 0 (dry) to 1 (wet)
+    pub moisture: f32,
+    /// From 0 (freezing) to 1 (scorching)
+    pub temperature: f32,
+}
+
+/// Climate of every tile of a map
+pub type ClimateMap = HashMap<(i32, i32), Climate>;
+
+/// Fractal noise brought back between 0 and 1
+fn get_smooth_noise(position: Vec2, seed: f32) -> f32 {
+    let amplitude_sum: f32 = (0..CLIMATE_OCTAVES)
+        .map(|octave| CLIMATE_GAIN.powi(octave as i32))
+        .sum();
+    let noise = fbm_simplex_2d_seeded(
+        position,
+        CLIMATE_OCTAVES,
+        CLIMATE_LACUNARITY,
+        CLIMATE_GAIN,
+        seed,
+    );
+    return (noise / amplitude_sum * 0.5 + 0.5).clamp(0., 1.);
+}
+
+/// Draw the climate of every tile.
+///
+/// `build_map` draws it before anything else from the PRNG instance of the
+/// map, so that `get_climate` can draw it again from the seed alone.
+pub fn draw_climate(pseudo_rng_instance: &mut StdRng, config: &MapGenConfig) -> ClimateMap {
+    // Seed of the height noise map
+    let noise_seed = draw_noise_seed(pseudo_rng_instance);
+
+    // Noise map parameters
+    let frequency_scale: f32 = pseudo_rng_instance.gen_range(config.noise.frequency.clone());
+    let amplitude_scale: f32 = pseudo_rng_instance.gen_range(config.noise.amplitude.clone());
+    let octaves: usize = pseudo_rng_instance.gen_range(config.noise.octaves.clone());
+    let lacunarity: f32 = pseudo_rng_instance.gen_range(config.noise.lacunarity.clone());
+    let gain: f32 = pseudo_rng_instance.gen_range(config.noise.gain.clone());
+
+    let moisture_seed = draw_noise_seed(pseudo_rng_instance);
+    let moisture_frequency =
+        pseudo_rng_instance.gen_range(config.climate.moisture_frequency.clone());
+    let temperature_seed = draw_noise_seed(pseudo_rng_instance);
+
+    let map_middle_h = (config.height as f32 / 2.).max(1.);
+
+    let mut climate = ClimateMap::new();
+    for w in 0..=config.width {
+        for h in 0..=config.height {
+            let p = vec2(w as f32, h as f32);
+            let elevation =
+                fbm_simplex_2d_seeded(p * frequency_scale, octaves, lacunarity, gain, noise_seed)
+                    * amplitude_scale
+                    * 0.015;
+
+            let moisture = get_smooth_noise(p * moisture_frequency, moisture_seed);
+
+            // Latitude bands, bent a little by noise, and highlands are colder
+            let latitude = (h as f32 - map_middle_h).abs() / map_middle_h;
+            let jitter = (get_smooth_noise(p * moisture_frequency, temperature_seed) - 0.5)
+                * 2.
+                * config.climate.temperature_jitter;
+            let cooling =
+                (elevation - config.thresholds.plain).max(0.) * config.climate.elevation_cooling;
+            let temperature = (1. - latitude + jitter - cooling).clamp(0., 1.);
+
+            climate.insert(
+                (w, h),
+                Climate {
+                    elevation,
+                    moisture,
+                    temperature,
+                },
+            );
+        }
+    }
+    return climate;
+}
+
+/// The climate of every tile of the map a seed builds, as it was before any
+/// edit
+pub fn get_climate(map_seed: &MapSeed, config: &MapGenConfig) -> ClimateMap {
+    return draw_climate(&mut StdRng::seed_from_u64(map_seed.0), config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> MapGenConfig {
+        return MapGenConfig {
+            width: 40,
+            height: 30,
+            ..MapGenConfig::default()
+        };
+    }
+
+    #[test]
+    fn moisture_and_temperature_stay_between_0_and_1() {
+        let config = small_config();
+        for seed in 0..5 {
+            let climate = get_climate(&MapSeed(seed), &config);
+            assert_eq!(
+                climate.len(),
+                ((config.width + 1) * (config.height + 1)) as usize
+            );
+            for tile_climate in climate.values() {
+                assert!((0. ..=1.).contains(&tile_climate.moisture));
+                assert!((0. ..=1.).contains(&tile_climate.temperature));
+                assert!(tile_climate.elevation.is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_climate() {
+        let config = small_config();
+        assert_eq!(
+            get_climate(&MapSeed(42), &config),
+            get_climate(&MapSeed(42), &config)
+        );
+        assert_ne!(
+            get_climate(&MapSeed(42), &config),
+            get_climate(&MapSeed(43), &config)
+        );
+    }
+}
@@ -0,0 +1,577 @@
+use std::{fmt, fs, ops::Range, path::PathBuf};
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::get_setting_value;
+
+/// Environment variable that can be used to select a generation preset
+const PRESET_ENV_VAR: &str = "SPRITESIM_PRESET";
+/// Command line argument that can be used to select a generation preset (takes
+/// precedence over the environment variable)
+const PRESET_ARG: &str = "--preset";
+/// Where presets live, relative to the assets folder
+const PRESETS_FOLDER: &str = "mapgen";
+
+/// Every knob of the map generation.
+///
+/// Ranges are not used as-is: a value is drawn inside each of them (from the
+/// MapSeed) every time a map is built, so that two seeds give two different
+/// worlds of the same «family».
+///
+/// Presets are RON files stored in `assets/mapgen`. Every omitted field falls
+/// back on its default value so presets only need to state what they change,
+/// while unknown (e.g. misspelled) fields are rejected.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapGenConfig {
+    /// In tiles
+    pub width: i32,
+    /// In tiles
+    pub height: i32,
+    pub noise: NoiseConfig,
+    pub thresholds: HeightThresholds,
+    pub climate: ClimateConfig,
+    pub biomes: BiomesConfig,
+    pub lakes: LakesConfig,
+    pub ocean: OceanConfig,
+    pub rivers: RiversConfig,
+    pub forests: PatchConfig,
+    pub specials: SpecialsConfig,
+    pub settlements: SettlementsConfig,
+}
+
+/// Parameters of the height noise map
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseConfig {
+    pub frequency: Range<f32>,
+    pub amplitude: Range<f32>,
+    pub octaves: Range<usize>,
+    pub lacunarity: Range<f32>,
+    pub gain: Range<f32>,
+}
+
+/// Height values from which a tile becomes a plain, a hill or a mountain.
+/// Anything below the plain threshold is ocean.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeightThresholds {
+    pub plain: f32,
+    pub hill: f32,
+    pub mountain: f32,
+}
+
+/// How moisture and temperature are spread (see `climate`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClimateConfig {
+    /// Frequency of the moisture noise map (the lower, the wider wet and dry
+    /// regions are)
+    pub moisture_frequency: Range<f32>,
+    /// How much (at most) noise warms or cools a tile from what its latitude
+    /// gives, so that climate bands are not straight
+    pub temperature_jitter: f32,
+    /// How much colder a tile gets per unit of elevation above the plain
+    /// threshold
+    pub elevation_cooling: f32,
+}
+
+/// Temperature and moisture thresholds between biomes (see `biome`), all
+/// between 0 and 1
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BiomesConfig {
+    /// Below this temperature, everything is snow
+    pub freezing: f32,
+    /// Below this temperature, everything is tundra
+    pub cold: f32,
+    /// From this temperature, biomes are tropical (deserts, savannas and
+    /// jungles)
+    pub hot: f32,
+    /// Below this moisture, biomes are dry (grasslands and deserts)
+    pub dry: f32,
+    /// From this moisture, biomes are wet (swamps and jungles)
+    pub wet: f32,
+}
+
+/// Which bodies of water are lakes (see `water`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LakesConfig {
+    /// Bodies of water that do not reach the edges of the map are lakes up to
+    /// this size (in tiles), and inland seas beyond
+    pub max_size: usize,
+}
+
+/// Where the ocean is shallow and where it is deep (see `water`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OceanConfig {
+    /// Sea tiles up to this distance (in tiles) from land are coastal waters
+    pub coast_width: usize,
+    /// Sea tiles less than this deep below the plain threshold are coastal
+    /// waters as well, however far from land
+    pub shallow_depth: f32,
+}
+
+/// How rivers are traced (see `river`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiversConfig {
+    /// How many rivers to lay (fewer may fit on a map without enough hills)
+    pub count: usize,
+    /// Rivers shorter than this (in tiles) are dropped
+    pub min_length: usize,
+    /// Lakes growing larger than this (in tiles) stop spilling over, and the
+    /// river ends there
+    pub max_lake_size: usize,
+}
+
+/// Parameters of a group of patches (see `generate_multiple_patches`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PatchConfig {
+    pub count: i32,
+    pub radius: Range<i32>,
+    pub frequency: Range<f32>,
+    pub amplitude: Range<f32>,
+}
+
+/// Probability for an eligible tile to receive a given special
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpecialsConfig {
+    pub corn: f64,
+    pub lumber: f64,
+    pub fish: f64,
+    pub freshwater_fish: f64,
+}
+
+/// How settlements are scattered on the map
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SettlementsConfig {
+    /// How many settlements to place (fewer may fit on a small map)
+    pub count: usize,
+    /// Minimum distance (in tiles) between two settlements
+    pub min_spacing: i32,
+    /// Distance (in tiles) up to which food specials attract settlements
+    pub food_radius: i32,
+    /// How much more likely a tile is to be settled per food special around it
+    pub food_weight: f32,
+}
+
+impl Default for MapGenConfig {
+    fn default() -> Self {
+        MapGenConfig {
+            width: 200,
+            height: 200,
+            noise: NoiseConfig::default(),
+            thresholds: HeightThresholds::default(),
+            climate: ClimateConfig::default(),
+            biomes: BiomesConfig::default(),
+            lakes: LakesConfig::default(),
+            ocean: OceanConfig::default(),
+            rivers: RiversConfig::default(),
+            forests: PatchConfig::default(),
+            specials: SpecialsConfig::default(),
+            settlements: SettlementsConfig::default(),
+        }
+    }
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            frequency: 0.03..0.06,
+            amplitude: 100.0..130.0,
+            octaves: 5..15,
+            lacunarity: 1.8..2.0,
+            gain: 0.5..0.6,
+        }
+    }
+}
+
+impl Default for HeightThresholds {
+    fn default() -> Self {
+        HeightThresholds {
+            plain: 0.,
+            hill: 1.3,
+            mountain: 1.8,
+        }
+    }
+}
+
+impl Default for ClimateConfig {
+    fn default() -> Self {
+        ClimateConfig {
+            moisture_frequency: 0.02..0.04,
+            temperature_jitter: 0.1,
+            elevation_cooling: 0.25,
+        }
+    }
+}
+
+impl Default for BiomesConfig {
+    fn default() -> Self {
+        BiomesConfig {
+            freezing: 0.08,
+            cold: 0.2,
+            hot: 0.7,
+            dry: 0.4,
+            wet: 0.62,
+        }
+    }
+}
+
+impl Default for LakesConfig {
+    fn default() -> Self {
+        LakesConfig { max_size: 300 }
+    }
+}
+
+impl Default for OceanConfig {
+    fn default() -> Self {
+        OceanConfig {
+            coast_width: 2,
+            shallow_depth: 0.3,
+        }
+    }
+}
+
+impl Default for RiversConfig {
+    fn default() -> Self {
+        RiversConfig {
+            count: 20,
+            min_length: 4,
+            max_lake_size: 12,
+        }
+    }
+}
+
+impl Default for PatchConfig {
+    fn default() -> Self {
+        PatchConfig {
+            count: 15,
+            radius: 1..3,
+            frequency: 0.05..1.0,
+            amplitude: 3.60..4.40,
+        }
+    }
+}
+
+impl Default for SpecialsConfig {
+    fn default() -> Self {
+        SpecialsConfig {
+            corn: 0.01,
+            lumber: 0.05,
+            fish: 0.01,
+            freshwater_fish: 0.03,
+        }
+    }
+}
+
+impl Default for SettlementsConfig {
+    fn default() -> Self {
+        SettlementsConfig {
+            count: 40,
+            min_spacing: 8,
+            food_radius: 2,
+            food_weight: 4.,
+        }
+    }
+}
+
+/// Everything that can go wrong when retrieving a MapGenConfig
+#[derive(Debug)]
+pub enum MapGenConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for MapGenConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MapGenConfigError::Io(path, error) => {
+                write!(f, "Cannot read «{}»: {}", path.display(), error)
+            }
+            MapGenConfigError::Parse(path, error) => {
+                write!(f, "Cannot parse «{}»: {}", path.display(), error)
+            }
+            MapGenConfigError::Invalid(reason) => write!(f, "Invalid configuration: {}", reason),
+        };
+    }
+}
+
+impl std::error::Error for MapGenConfigError {}
+
+/// Ensure a range can be drawn from
+fn check_range<T: PartialOrd + fmt::Debug>(
+    name: &str,
+    range: &Range<T>,
+) -> Result<(), MapGenConfigError> {
+    if range.start < range.end {
+        return Ok(());
+    }
+    return Err(MapGenConfigError::Invalid(format!(
+        "{} must be a non-empty range, got {:?}",
+        name, range
+    )));
+}
+
+/// Ensure float values are actual numbers, since the comparisons of the other
+/// checks let NaN through
+fn check_finite(name: &str, values: &[f32]) -> Result<(), MapGenConfigError> {
+    if values.iter().all(|value| value.is_finite()) {
+        return Ok(());
+    }
+    return Err(MapGenConfigError::Invalid(format!(
+        "{} must be finite, got {:?}",
+        name, values
+    )));
+}
+
+/// Ensure a probability is between 0 and 1
+fn check_probability(name: &str, value: f64) -> Result<(), MapGenConfigError> {
+    if (0.0..=1.0).contains(&value) {
+        return Ok(());
+    }
+    return Err(MapGenConfigError::Invalid(format!(
+        "{} must be between 0 and 1, got {}",
+        name, value
+    )));
+}
+
+impl MapGenConfig {
+    /// Retrieve the configuration from the preset given on the command line
+    /// (`--preset archipelago`), then from the `SPRITESIM_PRESET` environment
+    /// variable, and fall back on the default one if none was provided.
+    ///
+    /// A preset is either the name of a file in `assets/mapgen` (without its
+    /// extension) or a path to any RON file.
+    pub fn from_env() -> Result<Self, MapGenConfigError> {
+        return match get_setting_value(PRESET_ARG, PRESET_ENV_VAR) {
+            Some(preset) => {
+                let path = PathBuf::from(&preset);
+                if path.is_file() {
+                    MapGenConfig::load(path)
+                } else {
+                    MapGenConfig::load(
+                        FileAssetReader::get_base_path()
+                            .join("assets")
+                            .join(PRESETS_FOLDER)
+                            .join(format!("{}.ron", preset)),
+                    )
+                }
+            }
+            None => Ok(MapGenConfig::default()),
+        };
+    }
+
+    /// Read and validate a configuration from a RON file
+    pub fn load(path: PathBuf) -> Result<Self, MapGenConfigError> {
+        let content = fs::read_to_string(&path)
+            .map_err(|error| MapGenConfigError::Io(path.clone(), error))?;
+        let config: MapGenConfig =
+            ron::from_str(&content).map_err(|error| MapGenConfigError::Parse(path, error))?;
+        config.validate()?;
+        return Ok(config);
+    }
+
+    /// Ensure the configuration can actually produce a map
+    pub fn validate(&self) -> Result<(), MapGenConfigError> {
+        if self.width < 1 || self.height < 1 {
+            return Err(MapGenConfigError::Invalid(format!(
+                "map size must be positive, got {}x{}",
+                self.width, self.height
+            )));
+        }
+
+        let ranges = [
+            ("noise.frequency", &self.noise.frequency),
+            ("noise.amplitude", &self.noise.amplitude),
+            ("noise.lacunarity", &self.noise.lacunarity),
+            ("noise.gain", &self.noise.gain),
+            (
+                "climate.moisture_frequency",
+                &self.climate.moisture_frequency,
+            ),
+            ("forests.frequency", &self.forests.frequency),
+            ("forests.amplitude", &self.forests.amplitude),
+        ];
+        for (name, range) in ranges {
+            check_finite(name, &[range.start, range.end])?;
+        }
+        check_finite(
+            "thresholds",
+            &[
+                self.thresholds.plain,
+                self.thresholds.hill,
+                self.thresholds.mountain,
+            ],
+        )?;
+        check_finite(
+            "climate",
+            &[
+                self.climate.temperature_jitter,
+                self.climate.elevation_cooling,
+            ],
+        )?;
+        check_finite(
+            "biomes",
+            &[
+                self.biomes.freezing,
+                self.biomes.cold,
+                self.biomes.hot,
+                self.biomes.dry,
+                self.biomes.wet,
+            ],
+        )?;
+        check_finite("ocean.shallow_depth", &[self.ocean.shallow_depth])?;
+        check_finite("settlements.food_weight", &[self.settlements.food_weight])?;
+
+        check_range("noise.frequency", &self.noise.frequency)?;
+        check_range("noise.amplitude", &self.noise.amplitude)?;
+        check_range("noise.octaves", &self.noise.octaves)?;
+        check_range("noise.lacunarity", &self.noise.lacunarity)?;
+        check_range("noise.gain", &self.noise.gain)?;
+        if self.noise.octaves.start < 1 {
+            return Err(MapGenConfigError::Invalid(
+                "noise.octaves must start at 1 or more".to_string(),
+            ));
+        }
+
+        if !(self.thresholds.plain <= self.thresholds.hill
+            && self.thresholds.hill <= self.thresholds.mountain)
+        {
+            return Err(MapGenConfigError::Invalid(format!(
+                "thresholds must be ordered (plain <= hill <= mountain), got {:?}",
+                self.thresholds
+            )));
+        }
+
+        check_range(
+            "climate.moisture_frequency",
+            &self.climate.moisture_frequency,
+        )?;
+        if self.climate.temperature_jitter < 0. || self.climate.elevation_cooling < 0. {
+            return Err(MapGenConfigError::Invalid(
+                "climate values cannot be negative".to_string(),
+            ));
+        }
+
+        let biomes = &self.biomes;
+        if !(biomes.freezing <= biomes.cold
+            && biomes.cold <= biomes.hot
+            && biomes.dry <= biomes.wet)
+        {
+            return Err(MapGenConfigError::Invalid(format!(
+                "biomes thresholds must be ordered (freezing <= cold <= hot, dry <= wet), got {:?}",
+                biomes
+            )));
+        }
+
+        // Boats need coastal waters to set sail from the shore
+        if self.ocean.coast_width < 1 || self.ocean.shallow_depth < 0. {
+            return Err(MapGenConfigError::Invalid(format!(
+                "ocean.coast_width must be at least 1 and ocean.shallow_depth cannot be negative, got {:?}",
+                self.ocean
+            )));
+        }
+
+        if self.forests.count < 0 {
+            return Err(MapGenConfigError::Invalid(format!(
+                "forests.count cannot be negative, got {}",
+                self.forests.count
+            )));
+        }
+        check_range("forests.radius", &self.forests.radius)?;
+        check_range("forests.frequency", &self.forests.frequency)?;
+        check_range("forests.amplitude", &self.forests.amplitude)?;
+
+        check_probability("specials.corn", self.specials.corn)?;
+        check_probability("specials.lumber", self.specials.lumber)?;
+        check_probability("specials.fish", self.specials.fish)?;
+        check_probability("specials.freshwater_fish", self.specials.freshwater_fish)?;
+
+        if self.settlements.min_spacing < 1
+            || self.settlements.food_radius < 0
+            || self.settlements.food_weight < 0.
+        {
+            return Err(MapGenConfigError::Invalid(format!(
+                "settlements spacing must be positive and food settings cannot be negative, got {:?}",
+                self.settlements
+            )));
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load a configuration written in a temporary file
+    fn load_str(name: &str, content: &str) -> Result<MapGenConfig, MapGenConfigError> {
+        let path = std::env::temp_dir().join(format!("spritesim-config-{}.ron", name));
+        fs::write(&path, content).unwrap();
+        let config = MapGenConfig::load(path.clone());
+        fs::remove_file(path).unwrap();
+        return config;
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join(PRESETS_FOLDER);
+        for entry in fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(error) = MapGenConfig::load(path.clone()) {
+                panic!("{}: {}", path.display(), error);
+            }
+        }
+    }
+
+    #[test]
+    fn omitted_fields_keep_their_default() {
+        let config = load_str("omitted", "(width: 50)").unwrap();
+        assert_eq!(config.width, 50);
+        assert_eq!(config.noise, MapGenConfig::default().noise);
+    }
+
+    #[test]
+    fn unknown_field_is_rejected() {
+        let result = load_str("unknown", "(thresholds: (plain: 0.0, hil: 1.3))");
+        assert!(matches!(result, Err(MapGenConfigError::Parse(_, _))));
+    }
+
+    #[test]
+    fn nan_is_rejected() {
+        let result = load_str("nan", "(thresholds: (plain: NaN))");
+        assert!(matches!(result, Err(MapGenConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn unordered_thresholds_are_rejected() {
+        let mut config = MapGenConfig::default();
+        config.thresholds.hill = config.thresholds.mountain + 1.;
+        assert!(matches!(
+            config.validate(),
+            Err(MapGenConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn empty_range_is_rejected() {
+        let mut config = MapGenConfig::default();
+        config.noise.gain = 0.6..0.6;
+        assert!(matches!(
+            config.validate(),
+            Err(MapGenConfigError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+
+use super::{water::is_water, DevelopmentKind, FeatureKind, Kind, Layer, Map, Tile};
+
+/// Why a Development cannot be built on a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementError {
+    /// There is no tile at these coordinates
+    OutOfMap((i32, i32)),
+    /// The underlying layers do not allow it
+    Forbidden((i32, i32), DevelopmentKind),
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            PlacementError::OutOfMap(coordinates) => {
+                write!(f, "There is no tile at {:?}", coordinates)
+            }
+            PlacementError::Forbidden(coordinates, kind) => {
+                write!(
+                    f,
+                    "{:?} cannot be built on the tile at {:?}",
+                    kind, coordinates
+                )
+            }
+        };
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+/// Whether a Development can be built on a tile, depending on its terrain and
+/// feature:
+/// - Roads go anywhere but on water
+/// - Farmland and Irrigation need a flat land without any feature
+/// - Mines need a Hill (which Mountains are built upon)
+pub fn can_develop(tile: &Tile, kind: &DevelopmentKind) -> bool {
+    let feature_kind = tile.layers.get(&Layer::Feature);
+    return match kind {
+        DevelopmentKind::Road => !is_water(tile),
+        DevelopmentKind::Farmland | DevelopmentKind::Irrigation => feature_kind.is_none(),
+        DevelopmentKind::Mine => feature_kind == Some(&Kind::FKind(FeatureKind::Hill)),
+    };
+}
+
+/// Build a Development on a tile (replacing any previous one), provided the
+/// placement rules allow it.
+pub fn place_development(
+    map: &mut Map,
+    coordinates: &(i32, i32),
+    kind: DevelopmentKind,
+) -> Result<(), PlacementError> {
+    let tile = map
+        .get_mut(coordinates)
+        .ok_or(PlacementError::OutOfMap(*coordinates))?;
+    if !can_develop(tile, &kind) {
+        return Err(PlacementError::Forbidden(*coordinates, kind));
+    }
+    tile.layers.insert(Layer::Development, Kind::DKind(kind));
+    return Ok(());
+}
+
+/// Remove the Development of a tile, if any
+pub fn remove_development(map: &mut Map, coordinates: &(i32, i32)) -> Option<DevelopmentKind> {
+    return match map.get_mut(coordinates)?.layers.remove(&Layer::Development) {
+        Some(Kind::DKind(kind)) => Some(kind),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::mapgen::{
+        autotile::{get_tiles_to_display, AutotileRules},
+        tests::draw_map,
+    };
+
+    #[test]
+    fn no_farmland_on_water() {
+        let mut map = draw_map(&["~.~"]);
+        assert_eq!(
+            place_development(&mut map, &(0, 0), DevelopmentKind::Farmland),
+            Err(PlacementError::Forbidden((0, 0), DevelopmentKind::Farmland))
+        );
+        assert!(place_development(&mut map, &(1, 0), DevelopmentKind::Farmland).is_ok());
+        assert!(place_development(&mut map, &(2, 0), DevelopmentKind::Irrigation).is_err());
+        assert_eq!(
+            map[&(1, 0)].layers.get(&Layer::Development),
+            Some(&Kind::DKind(DevelopmentKind::Farmland))
+        );
+    }
+
+    #[test]
+    fn mines_only_on_hills_and_mountains() {
+        let mut map = draw_map(&[".hmf"]);
+        assert!(place_development(&mut map, &(0, 0), DevelopmentKind::Mine).is_err());
+        assert!(place_development(&mut map, &(1, 0), DevelopmentKind::Mine).is_ok());
+        assert!(place_development(&mut map, &(2, 0), DevelopmentKind::Mine).is_ok());
+        assert!(place_development(&mut map, &(3, 0), DevelopmentKind::Mine).is_err());
+    }
+
+    #[test]
+    fn no_roads_on_water() {
+        let mut map = draw_map(&["h~"]);
+        assert!(place_development(&mut map, &(0, 0), DevelopmentKind::Road).is_ok());
+        assert!(place_development(&mut map, &(1, 0), DevelopmentKind::Road).is_err());
+        assert_eq!(
+            place_development(&mut map, &(5, 5), DevelopmentKind::Road),
+            Err(PlacementError::OutOfMap((5, 5)))
+        );
+    }
+
+    #[test]
+    fn developments_can_be_removed() {
+        let mut map = draw_map(&["=."]);
+        assert_eq!(
+            remove_development(&mut map, &(0, 0)),
+            Some(DevelopmentKind::Road)
+        );
+        assert_eq!(remove_development(&mut map, &(0, 0)), None);
+        assert_eq!(remove_development(&mut map, &(1, 0)), None);
+    }
+
+    #[test]
+    fn roads_connect_to_neighbouring_roads() {
+        let rules = AutotileRules::load(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/autotile/cardinal_16.ron"),
+        )
+        .unwrap();
+        let mut map = draw_map(&["...", ".=.", "..."]);
+        let get_index = |map: &Map| {
+            return get_tiles_to_display(
+                &map[&(1, 1)],
+                map,
+                &(1, 1),
+                Layer::Development,
+                Some(&rules),
+            )
+            .0;
+        };
+        // A lone road, then one going left to right, then a crossing (see the
+        // connection bitmask of cardinal_16.ron)
+        assert_eq!(get_index(&map), 0);
+        place_development(&mut map, &(0, 1), DevelopmentKind::Road).unwrap();
+        place_development(&mut map, &(2, 1), DevelopmentKind::Road).unwrap();
+        assert_eq!(get_index(&map), 6);
+        place_development(&mut map, &(1, 2), DevelopmentKind::Road).unwrap();
+        place_development(&mut map, &(1, 0), DevelopmentKind::Road).unwrap();
+        assert_eq!(get_index(&map), 15);
+        // Diagonals do not connect
+        place_development(&mut map, &(0, 0), DevelopmentKind::Road).unwrap();
+        assert_eq!(get_index(&map), 15);
+    }
+}
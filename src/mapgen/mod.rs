@@ -0,0 +1,624 @@
+//! Headless world generation.
+//!
+//! Everything needed to build a Map lives here, without any rendering concern
+//! (no AssetServer, no Commands), so worlds can be generated and inspected
+//! without a window or a GPU.
+
+use bevy::{math::vec2, prelude::*, utils::HashMap};
+use noisy_bevy::simplex_noise_2d_seeded;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::get_setting_value;
+use climate::Climate;
+use config::{MapGenConfig, PatchConfig};
+use water::WaterBodyId;
+
+pub mod autotile;
+pub mod biome;
+pub mod climate;
+pub mod config;
+pub mod development;
+pub mod painting;
+pub mod pathfinding;
+pub mod query;
+pub mod river;
+pub mod save;
+pub mod settlement;
+pub mod unit;
+pub mod water;
+
+/// Size (in pixels) of a tile once drawn
+pub const SPRITE_SIZE: f32 = 16.;
+
+/// Environment variable that can be used to force the map seed
+const SEED_ENV_VAR: &str = "SPRITESIM_SEED";
+/// Command line argument that can be used to force the map seed (takes
+/// precedence over the environment variable)
+const SEED_ARG: &str = "--seed";
+
+/// Noise seeds are added to the lattice coordinates noisy_bevy hashes, which
+/// wrap around every 289 cells: larger seeds only bring f32 rounding, which
+/// collapses neighbouring cells to the same hash.
+const NOISE_SEED_RANGE: f32 = 289.;
+
+/// The seed every random draw of the map generation derives from.
+///
+/// A given seed always yields the exact same Map, so a world can be shared
+/// (or a bug reproduced) by passing its seed around.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MapSeed(pub u64);
+
+impl MapSeed {
+    /// Retrieve the seed from the command line (`--seed 42` or `--seed=42`),
+    /// then from the `SPRITESIM_SEED` environment variable, and fall back
+    /// on a random one if none was provided.
+    pub fn from_env() -> Self {
+        return match get_setting_value(SEED_ARG, SEED_ENV_VAR) {
+            Some(value) => MapSeed(
+                value
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid map seed «{}»", value)),
+            ),
+            None => MapSeed(StdRng::from_entropy().gen()),
+        };
+    }
+}
+
+/// Draw the seed of a noise map
+pub(crate) fn draw_noise_seed(pseudo_rng_instance: &mut StdRng) -> f32 {
+    return pseudo_rng_instance.gen_range(0. ..NOISE_SEED_RANGE);
+}
+
+/// A Tile is made of several layers, from bottom to top (only the first one is
+/// mandatory, the other are all optional):
+/// - A base Terrain (Plain, Desert, etc.)
+/// - a Feature (Forest, Hills, etc.)
+/// - a Special characteristic (Food, Ore, Silver, etc.)
+/// - a Development (Road, Farmland, etc.)
+/// - a Settlement (Village, Fort, etc.)
+/// - a Unit (Settler, Canon, etc.) that is moving through it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Layer {
+    Terrain,
+    Feature,
+    Special,
+    Development,
+    Settlement,
+}
+
+impl Layer {
+    /// Every layer, from bottom to top
+    pub const ALL: [Layer; 5] = [
+        Layer::Terrain,
+        Layer::Feature,
+        Layer::Special,
+        Layer::Development,
+        Layer::Settlement,
+    ];
+}
+
+/// Terrain are the base layers of all tiles (see `biome` for where each of
+/// them lies)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TerrainKind {
+    Desert,
+    Plain,
+    Tundra,
+    Snow,
+    Grassland,
+    Savanna,
+    Jungle,
+    Swamp,
+}
+
+/// Features are natural characteristics that add value to a tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FeatureKind {
+    Forest,
+    /// Shallow waters along the coasts (see `water` for where the ocean gets
+    /// deep)
+    Ocean,
+    Hill,
+    River,
+    Lake,
+    DeepOcean,
+}
+
+/// Special are particulary rich deposits that add even more value to a tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpecialKind {
+    Lumber,
+    Corn,
+    Fish,
+    Mountain,
+    FreshwaterFish,
+}
+
+/// Developments are improvements built on a tile (see `development` for where
+/// each of them can be built)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DevelopmentKind {
+    Road,
+    Farmland,
+    Irrigation,
+    Mine,
+}
+
+/// Settlements are where people live (see `settlement` for how they are
+/// scattered on the map)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum SettlementKind {
+    Village,
+    Town,
+    City,
+    Fort,
+}
+
+/// Units move through tiles rather than being part of them, so they are not
+/// stored in the Map (see `unit` for where each of them can go)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum UnitKind {
+    Settler,
+    Worker,
+    Soldier,
+    Cannon,
+    Boat,
+    Ship,
+}
+
+/// This is a union of all sprites types. Used for using common sprite
+/// drawing functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+    TKind(TerrainKind),
+    FKind(FeatureKind),
+    SKind(SpecialKind),
+    DKind(DevelopmentKind),
+    StKind(SettlementKind),
+}
+
+/// In-memory map for all layers of a Tile
+pub type TileLayers = HashMap<Layer, Kind>;
+
+/// A «Tile» is a superposition of several things that will compose the Map.
+#[derive(Clone, Debug)]
+pub struct Tile {
+    pub layers: TileLayers,
+
+    // These are called «real» coordinates because they are not the coordinates
+    // in the map, but rather are the coordinates of where the sprite will be drawn
+    pub real_coordinates: (f32, f32),
+
+    /// What the Kinds of the tile were derived from (see `climate`)
+    pub climate: Climate,
+
+    /// The body of water the tile is part of, if it is under water (see
+    /// `water`)
+    pub water_body: Option<WaterBodyId>,
+}
+
+/// Retrieve the related layer of a Kind
+pub fn get_kind_of_tile_layer(tile: &Tile, layer: &Layer) -> Option<Kind> {
+    return tile.layers.get(layer).map(|layer| layer.clone());
+}
+
+/// Retrieve the concrete Kind of a tile on a given Layer
+pub fn get_layer_from_kind(kind: &Kind) -> Layer {
+    return match kind {
+        Kind::TKind(_) => Layer::Terrain,
+        Kind::FKind(_) => Layer::Feature,
+        Kind::SKind(_) => Layer::Special,
+        Kind::DKind(_) => Layer::Development,
+        Kind::StKind(_) => Layer::Settlement,
+    };
+}
+
+/// In-memory map for all gameplay and render purposes.
+/// This is the heart of the game.
+pub type Map = HashMap<(i32, i32), Tile>;
+
+/// Generates several terrain patches in one go.
+///
+/// Use this function to avoid having to place patches one by one.
+/// Patches are put in a kinda equidistant positions (based on their count), and
+/// every parameter is randomly adjusted to simulate realism and RNG
+fn generate_multiple_patches(
+    pseudo_rng_instance: &mut StdRng,
+    map: &mut Map,
+    config: &MapGenConfig,
+    kind: Kind,
+    patch_config: &PatchConfig,
+) {
+    // Positions patches centers on the map
+    // (kinda equidistant, but with random variations)
+    let max_offset = 5;
+    let count = patch_config.count;
+    let mut patch_centers: Vec<(i32, i32)> = Vec::new();
+    for w in 1..count {
+        for h in 1..count {
+            patch_centers.push((
+                pseudo_rng_instance.gen_range(-max_offset..=max_offset) + config.width * w / count,
+                pseudo_rng_instance.gen_range(-max_offset..=max_offset) + config.height * h / count,
+            ));
+        }
+    }
+
+    // Main generation process
+    for coordinates in patch_centers {
+        let radius = pseudo_rng_instance.gen_range(patch_config.radius.clone()) as f32;
+        let frequency_scale = pseudo_rng_instance.gen_range(patch_config.frequency.clone());
+        let amplitude_scale = pseudo_rng_instance.gen_range(patch_config.amplitude.clone());
+        let grid_half_size = radius as i32 + 1;
+        for w in -grid_half_size..=grid_half_size {
+            for h in -grid_half_size..=grid_half_size {
+                // Compute noise offset (That will contribute to the "blob" shape
+                // the patch will have)
+                let offset = simplex_noise_2d_seeded(
+                    vec2(w as f32, h as f32) * frequency_scale,
+                    draw_noise_seed(pseudo_rng_instance),
+                ) * amplitude_scale;
+
+                // Height will serve, with a threshold cutoff, as sizing the resulting patch
+                let height = radius + offset - ((w * w + h * h) as f32).sqrt();
+                let height_threshold = 0.;
+
+                let key = (
+                    // No sense in adding tiles outside of the map
+                    (coordinates.0 + w).clamp(1, (config.width - 1).max(1)),
+                    (coordinates.1 + h).clamp(1, (config.height - 1).max(1)),
+                );
+
+                let tile = map.get(&key).unwrap();
+                let layers = tile.layers.clone();
+                let climate = tile.climate;
+                let water_body = tile.water_body;
+
+                // Here we go !
+                if
+                // Height threshold for size the shape
+                (height > height_threshold) &&
+                // Only replace tile when necessary (for instance, Forest tiles can only be placed on some terrains)
+                ( kind != Kind::FKind(FeatureKind::Forest)
+                  || matches!(layers.get(&Layer::Terrain), Some(Kind::TKind(terrain)) if biome::can_grow_forest(terrain))
+                     && layers.get(&Layer::Feature).is_none())
+                {
+                    let screen_coordinates =
+                        (key.0 as f32 * SPRITE_SIZE, key.1 as f32 * SPRITE_SIZE);
+                    let mut existing_tile_layers = layers.clone();
+
+                    // @TODO Hack for regular terrain generation, should be better handled
+                    existing_tile_layers.remove(&Layer::Feature);
+
+                    map.insert(key, {
+                        existing_tile_layers.insert(get_layer_from_kind(&kind), kind);
+                        Tile {
+                            layers: existing_tile_layers,
+                            real_coordinates: screen_coordinates,
+                            climate,
+                            water_body,
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Only used in building the map
+fn update_tile_in_map(
+    map: &mut Map,
+    coordinates: &(i32, i32),
+    terrain_kind: Option<&TerrainKind>,
+    feature_kind: Option<&FeatureKind>,
+    special_kind: Option<&SpecialKind>,
+) {
+    map.insert(coordinates.clone(), {
+        Tile {
+            layers: {
+                let mut layers = match map.get(coordinates) {
+                    Some(tile) => tile.layers.clone(),
+                    None => TileLayers::new(),
+                };
+                match terrain_kind {
+                    Some(kind) => {
+                        layers.insert(Layer::Terrain, Kind::TKind(kind.clone()));
+                    }
+                    _ => {}
+                };
+                match feature_kind {
+                    Some(kind) => {
+                        layers.insert(Layer::Feature, Kind::FKind(kind.clone()));
+                    }
+                    _ => {}
+                };
+                match special_kind {
+                    Some(kind) => {
+                        layers.insert(Layer::Special, Kind::SKind(kind.clone()));
+                    }
+                    _ => {}
+                };
+                layers
+            },
+            real_coordinates: (
+                (coordinates.0 as f32) * SPRITE_SIZE,
+                (coordinates.1 as f32) * SPRITE_SIZE,
+            ),
+            climate: map
+                .get(coordinates)
+                .map(|tile| tile.climate)
+                .unwrap_or_default(),
+            water_body: map.get(coordinates).and_then(|tile| tile.water_body),
+        }
+    });
+}
+
+/// Main map building function.
+///
+/// The map seed is used to derive the PRNG instance generating the seeds for
+/// the different layers (patch groups) applied on the map, while the generation
+/// config holds the size and the ranges in which every parameter is drawn.
+/// Same seed and same config, same map.
+pub fn build_map(map_seed: &MapSeed, config: &MapGenConfig) -> Map {
+    let mut pseudo_rng_instance = StdRng::seed_from_u64(map_seed.0);
+    let mut map: Map = HashMap::new();
+
+    // Elevation (the height noise map), moisture and temperature of every tile
+    let climate = climate::draw_climate(&mut pseudo_rng_instance, config);
+
+    // Initialize the whole map terrains
+    for w in 0..=config.width {
+        for h in 0..=config.height {
+            let tile_climate = climate[&(w, h)];
+            let offset = tile_climate.elevation;
+
+            // For regular terrain tiles, the climate (mostly driven by the
+            // latitude) picks the biome, to simulate the earth distribution.
+            let base_terrain = biome::get_biome(&tile_climate, &config.biomes);
+
+            let plain_threshold = config.thresholds.plain;
+            let hill_threshold = config.thresholds.hill;
+            let mountain_threshold = config.thresholds.mountain;
+
+            // Depending on the offset (the point "height" in the noise map),
+            // we will have either an Ocean tile or a regular terrain tile.
+            match offset {
+                o if o >= plain_threshold && o < hill_threshold => {
+                    update_tile_in_map(&mut map, &(w, h), Some(&base_terrain), None, None);
+                }
+                o if o >= hill_threshold && o < mountain_threshold => {
+                    update_tile_in_map(
+                        &mut map,
+                        &(w, h),
+                        Some(&base_terrain),
+                        Some(&FeatureKind::Hill),
+                        None,
+                    );
+                }
+                o if o >= mountain_threshold => {
+                    update_tile_in_map(
+                        &mut map,
+                        &(w, h),
+                        Some(&base_terrain),
+                        Some(&FeatureKind::Hill),
+                        Some(&SpecialKind::Mountain),
+                    );
+                }
+                _ => {
+                    update_tile_in_map(
+                        &mut map,
+                        &(w, h),
+                        Some(&base_terrain),
+                        Some(&FeatureKind::Ocean),
+                        None,
+                    );
+                }
+            }
+            map.get_mut(&(w, h)).unwrap().climate = tile_climate;
+        }
+    }
+
+    // Seas that do not reach the edges of the map are lakes, and the open sea
+    // is deep
+    water::place_lakes(&mut map, config);
+    water::place_deep_ocean(&mut map, config);
+
+    // Rivers go first, so that forests grow around them
+    river::place_rivers(&mut pseudo_rng_instance, &mut map, config);
+
+    //    Generate random patches of Forests
+    generate_multiple_patches(
+        &mut pseudo_rng_instance,
+        &mut map,
+        config,
+        Kind::FKind(FeatureKind::Forest),
+        &config.forests,
+    );
+
+    // Place specials
+    for w in 0..=config.width {
+        for h in 0..=config.height {
+            let tile = map.get(&(w, h)).unwrap();
+            let terrain_kind = tile.layers.get(&Layer::Terrain).unwrap();
+            let feature_kind = tile.layers.get(&Layer::Feature);
+            // let special_kind = tile.layers.get(&Layer::Special);
+            match (w, h) {
+                // Corn goes on feature-less fertile land
+                (w, h)
+                    if matches!(terrain_kind, Kind::TKind(terrain) if biome::can_grow_corn(terrain))
+                        && feature_kind == None
+                        && pseudo_rng_instance.gen_bool(config.specials.corn) =>
+                {
+                    update_tile_in_map(&mut map, &(w, h), None, None, Some(&SpecialKind::Corn))
+                }
+                // Lumber goes on forests
+                (w, h)
+                    if feature_kind == Some(&Kind::FKind(FeatureKind::Forest))
+                        && pseudo_rng_instance.gen_bool(config.specials.lumber) =>
+                {
+                    update_tile_in_map(&mut map, &(w, h), None, None, Some(&SpecialKind::Lumber))
+                }
+                // Fish goes on coastal waters
+                (w, h)
+                    if feature_kind == Some(&Kind::FKind(FeatureKind::Ocean))
+                        && pseudo_rng_instance.gen_bool(config.specials.fish) =>
+                {
+                    update_tile_in_map(&mut map, &(w, h), None, None, Some(&SpecialKind::Fish))
+                }
+                // Freshwater fish goes on lakes
+                (w, h)
+                    if feature_kind == Some(&Kind::FKind(FeatureKind::Lake))
+                        && pseudo_rng_instance.gen_bool(config.specials.freshwater_fish) =>
+                {
+                    update_tile_in_map(
+                        &mut map,
+                        &(w, h),
+                        None,
+                        None,
+                        Some(&SpecialKind::FreshwaterFish),
+                    )
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Place settlements, now that we know where food is
+    settlement::place_settlements(&mut pseudo_rng_instance, &mut map, config);
+
+    water::label_water_bodies(&mut map);
+
+    return map;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a map from rows of characters (the first row being the top one),
+    /// so that tests can draw small maps by hand. Every tile is a Plain, with:
+    /// - `.` nothing more
+    /// - `f` a Forest, `h` a Hill and `m` a Mountain (on a Hill)
+    /// - `~` an Ocean, `d` a Deep Ocean and `l` a Lake
+    /// - `=` a Road
+    pub(crate) fn draw_map(rows: &[&str]) -> Map {
+        let mut map: Map = HashMap::new();
+        for (row, line) in rows.iter().enumerate() {
+            let h = (rows.len() - 1 - row) as i32;
+            for (w, character) in line.chars().enumerate() {
+                let w = w as i32;
+                let mut layers = TileLayers::new();
+                layers.insert(Layer::Terrain, Kind::TKind(TerrainKind::Plain));
+                let feature = match character {
+                    'f' => Some(FeatureKind::Forest),
+                    'h' | 'm' => Some(FeatureKind::Hill),
+                    '~' => Some(FeatureKind::Ocean),
+                    'd' => Some(FeatureKind::DeepOcean),
+                    'l' => Some(FeatureKind::Lake),
+                    _ => None,
+                };
+                if let Some(feature) = feature {
+                    layers.insert(Layer::Feature, Kind::FKind(feature));
+                }
+                if character == 'm' {
+                    layers.insert(Layer::Special, Kind::SKind(SpecialKind::Mountain));
+                }
+                if character == '=' {
+                    layers.insert(Layer::Development, Kind::DKind(DevelopmentKind::Road));
+                }
+                map.insert(
+                    (w, h),
+                    Tile {
+                        layers,
+                        real_coordinates: (w as f32 * SPRITE_SIZE, h as f32 * SPRITE_SIZE),
+                        climate: Climate::default(),
+                        water_body: None,
+                    },
+                );
+            }
+        }
+        water::label_water_bodies(&mut map);
+        return map;
+    }
+
+    /// FNV-1a hash of every tile (coordinates, Kinds, climate and water body),
+    /// in sorted order so that it does not depend on the HashMaps
+    fn hash_map(map: &Map) -> u64 {
+        let mut coordinates: Vec<&(i32, i32)> = map.keys().collect();
+        coordinates.sort();
+
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for coordinates in coordinates {
+            let tile = &map[coordinates];
+            let mut kinds: Vec<String> = tile
+                .layers
+                .values()
+                .map(|kind| format!("{:?}", kind))
+                .collect();
+            kinds.sort();
+            let description = format!(
+                "{:?}{:?}{:?}{:?}",
+                coordinates, kinds, tile.climate, tile.water_body
+            );
+            for byte in description.bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        return hash;
+    }
+
+    /// A small map, quick to build
+    fn small_config() -> MapGenConfig {
+        return MapGenConfig {
+            width: 30,
+            height: 20,
+            ..MapGenConfig::default()
+        };
+    }
+
+    #[test]
+    fn builds_every_tile() {
+        let config = small_config();
+        assert!(config.validate().is_ok());
+        let map = build_map(&MapSeed(7), &config);
+        assert_eq!(map.len(), 31 * 21);
+        for w in 0..=config.width {
+            for h in 0..=config.height {
+                let tile = &map[&(w, h)];
+                assert!(tile.layers.contains_key(&Layer::Terrain));
+                assert_eq!(
+                    tile.real_coordinates,
+                    (w as f32 * SPRITE_SIZE, h as f32 * SPRITE_SIZE)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_map_is_rejected() {
+        let config = MapGenConfig {
+            width: 0,
+            ..small_config()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn same_seed_builds_same_map() {
+        let config = MapGenConfig::default();
+        let first = hash_map(&build_map(&MapSeed(42), &config));
+        let second = hash_map(&build_map(&MapSeed(42), &config));
+        assert_eq!(first, second);
+        assert_ne!(first, hash_map(&build_map(&MapSeed(43), &config)));
+    }
+
+    /// Changes to this value mean that generation changed: update it if that
+    /// was intended, since every shared seed now gives another world
+    #[test]
+    fn seed_builds_pinned_map() {
+        let map = build_map(&MapSeed(42), &MapGenConfig::default());
+        assert_eq!(hash_map(&map), 16169309613992989206);
+    }
+}
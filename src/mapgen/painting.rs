@@ -0,0 +1,222 @@
+use std::fmt;
+
+use super::{
+    biome::{can_grow_corn, can_grow_forest},
+    development::can_develop,
+    get_layer_from_kind,
+    settlement::can_settle,
+    FeatureKind, Kind, Layer, Map, SpecialKind, Tile, TileLayers,
+};
+
+/// Why a Kind cannot be painted on a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaintError {
+    /// There is no tile at these coordinates
+    OutOfMap((i32, i32)),
+    /// The underlying layers do not allow it, or it would wipe out a
+    /// settlement
+    Forbidden((i32, i32), Kind),
+}
+
+impl fmt::Display for PaintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            PaintError::OutOfMap(coordinates) => {
+                write!(f, "There is no tile at {:?}", coordinates)
+            }
+            PaintError::Forbidden(coordinates, kind) => {
+                write!(
+                    f,
+                    "{:?} cannot be painted on the tile at {:?}",
+                    kind, coordinates
+                )
+            }
+        };
+    }
+}
+
+impl std::error::Error for PaintError {}
+
+/// Whether a tile can hold a Kind given its lower layers, following the same
+/// rules as generation:
+/// - Terrains, Oceans (coastal or deep), Lakes, Hills and Rivers go anywhere
+/// - Forests only grow on some terrains, and Corn needs a feature-less one of
+///   its own terrains (see `biome`)
+/// - Lumber needs a forest, Fish coastal waters, Freshwater Fish a lake and
+///   Mountains a hill
+/// - Developments and settlements have their own rules (see `development`
+///   and `settlement`)
+pub fn can_hold(tile: &Tile, kind: &Kind) -> bool {
+    let terrain_kind = tile.layers.get(&Layer::Terrain);
+    let feature_kind = tile.layers.get(&Layer::Feature);
+    return match kind {
+        Kind::TKind(_) => true,
+        Kind::FKind(FeatureKind::Forest) => {
+            matches!(terrain_kind, Some(Kind::TKind(terrain)) if can_grow_forest(terrain))
+        }
+        Kind::FKind(
+            FeatureKind::Ocean
+            | FeatureKind::DeepOcean
+            | FeatureKind::Lake
+            | FeatureKind::Hill
+            | FeatureKind::River,
+        ) => true,
+        Kind::SKind(SpecialKind::Corn) => {
+            matches!(terrain_kind, Some(Kind::TKind(terrain)) if can_grow_corn(terrain))
+                && feature_kind.is_none()
+        }
+        Kind::SKind(SpecialKind::Lumber) => feature_kind == Some(&Kind::FKind(FeatureKind::Forest)),
+        Kind::SKind(SpecialKind::Fish) => feature_kind == Some(&Kind::FKind(FeatureKind::Ocean)),
+        Kind::SKind(SpecialKind::FreshwaterFish) => {
+            feature_kind == Some(&Kind::FKind(FeatureKind::Lake))
+        }
+        Kind::SKind(SpecialKind::Mountain) => feature_kind == Some(&Kind::FKind(FeatureKind::Hill)),
+        Kind::DKind(kind) => can_develop(tile, kind),
+        Kind::StKind(_) => can_settle(tile),
+    };
+}
+
+/// Paint a Kind on a tile, replacing whatever its layer held.
+///
+/// Upper layers that no longer make sense (like Fish on a tile that is not an
+/// ocean anymore) are removed, except settlements: painting is refused rather
+/// than wiping them out. Settlements themselves cannot be painted, since they
+/// are more than a tile layer.
+///
+/// The previous layers of the tile are returned, so that the change can be
+/// undone, or None if the tile was left untouched.
+pub fn paint_tile(tile: &mut Tile, kind: Kind) -> Option<TileLayers> {
+    if matches!(kind, Kind::StKind(_)) || !can_hold(tile, &kind) {
+        return None;
+    }
+
+    let previous_layers = tile.layers.clone();
+    let painted_layer = get_layer_from_kind(&kind);
+    tile.layers.insert(painted_layer, kind);
+    for layer in Layer::ALL
+        .iter()
+        .skip_while(|layer| **layer != painted_layer)
+        .skip(1)
+    {
+        let Some(upper_kind) = tile.layers.get(layer).copied() else {
+            continue;
+        };
+        if can_hold(tile, &upper_kind) {
+            continue;
+        }
+        if *layer == Layer::Settlement {
+            tile.layers = previous_layers;
+            return None;
+        }
+        tile.layers.remove(layer);
+    }
+
+    return Some(previous_layers);
+}
+
+/// Whether `paint_tile` would paint a Kind on a tile, without touching it
+pub fn can_paint(tile: &Tile, kind: Kind) -> bool {
+    return paint_tile(&mut tile.clone(), kind).is_some();
+}
+
+/// Paint a Kind on the tile at the given coordinates (see `paint_tile`)
+pub fn paint(
+    map: &mut Map,
+    coordinates: &(i32, i32),
+    kind: Kind,
+) -> Result<TileLayers, PaintError> {
+    let tile = map
+        .get_mut(coordinates)
+        .ok_or(PaintError::OutOfMap(*coordinates))?;
+    return paint_tile(tile, kind).ok_or(PaintError::Forbidden(*coordinates, kind));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::{tests::draw_map, DevelopmentKind, SettlementKind, TerrainKind};
+
+    #[test]
+    fn settlements_are_never_wiped_out() {
+        let mut map = draw_map(&["."]);
+        let village = Kind::StKind(SettlementKind::Village);
+        map.get_mut(&(0, 0))
+            .unwrap()
+            .layers
+            .insert(Layer::Settlement, village);
+        let layers = map[&(0, 0)].layers.clone();
+
+        let ocean = Kind::FKind(FeatureKind::Ocean);
+        assert!(!can_paint(&map[&(0, 0)], ocean));
+        assert_eq!(
+            paint(&mut map, &(0, 0), ocean),
+            Err(PaintError::Forbidden((0, 0), ocean))
+        );
+        assert_eq!(map[&(0, 0)].layers, layers);
+        assert!(paint(&mut map, &(0, 0), village).is_err());
+
+        // A Hill does not bother a village
+        assert_eq!(
+            paint(&mut map, &(0, 0), Kind::FKind(FeatureKind::Hill)),
+            Ok(layers)
+        );
+        assert_eq!(map[&(0, 0)].layers.get(&Layer::Settlement), Some(&village));
+    }
+
+    #[test]
+    fn upper_layers_are_pruned() {
+        let mut map = draw_map(&["f."]);
+        let tile = map.get_mut(&(0, 0)).unwrap();
+        tile.layers
+            .insert(Layer::Special, Kind::SKind(SpecialKind::Lumber));
+        let forest_layers = tile.layers.clone();
+        assert_eq!(
+            paint(&mut map, &(0, 0), Kind::FKind(FeatureKind::Ocean)),
+            Ok(forest_layers)
+        );
+        assert_eq!(
+            map[&(0, 0)].layers.get(&Layer::Feature),
+            Some(&Kind::FKind(FeatureKind::Ocean))
+        );
+        assert_eq!(map[&(0, 0)].layers.get(&Layer::Special), None);
+
+        // Corn only grows on plains, while farmland does not care about the
+        // terrain
+        let tile = map.get_mut(&(1, 0)).unwrap();
+        tile.layers
+            .insert(Layer::Special, Kind::SKind(SpecialKind::Corn));
+        tile.layers
+            .insert(Layer::Development, Kind::DKind(DevelopmentKind::Farmland));
+        assert!(paint(&mut map, &(1, 0), Kind::TKind(TerrainKind::Desert)).is_ok());
+        assert_eq!(map[&(1, 0)].layers.get(&Layer::Special), None);
+        assert_eq!(
+            map[&(1, 0)].layers.get(&Layer::Development),
+            Some(&Kind::DKind(DevelopmentKind::Farmland))
+        );
+    }
+
+    #[test]
+    fn kinds_that_cannot_be_held_are_refused() {
+        let mut map = draw_map(&["."]);
+        let layers = map[&(0, 0)].layers.clone();
+        for kind in [
+            Kind::SKind(SpecialKind::Fish),
+            Kind::SKind(SpecialKind::Lumber),
+            Kind::SKind(SpecialKind::Mountain),
+            Kind::DKind(DevelopmentKind::Mine),
+        ] {
+            assert!(!can_hold(&map[&(0, 0)], &kind));
+            assert!(!can_paint(&map[&(0, 0)], kind));
+            assert_eq!(paint_tile(map.get_mut(&(0, 0)).unwrap(), kind), None);
+            assert_eq!(
+                paint(&mut map, &(0, 0), kind),
+                Err(PaintError::Forbidden((0, 0), kind))
+            );
+            assert_eq!(map[&(0, 0)].layers, layers);
+        }
+        assert_eq!(
+            paint(&mut map, &(1, 0), Kind::SKind(SpecialKind::Corn)),
+            Err(PaintError::OutOfMap((1, 0)))
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::{prelude::Resource, utils::HashMap};
+
+use super::{
+    autotile::Neighbour,
+    unit::{can_enter, sails},
+    DevelopmentKind, FeatureKind, Kind, Layer, Map, SpecialKind, TerrainKind, Tile, UnitKind,
+};
+
+/// Which tiles are considered next to each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Only through edges (top, left, right, bottom)
+    Four,
+    /// Through edges and corners
+    Eight,
+}
+
+impl Connectivity {
+    fn get_neighbours(&self) -> &'static [Neighbour] {
+        return match self {
+            Connectivity::Four => &[
+                Neighbour::Top,
+                Neighbour::Left,
+                Neighbour::Right,
+                Neighbour::Bottom,
+            ],
+            Connectivity::Eight => &Neighbour::ALL,
+        };
+    }
+
+    /// Least number of steps between two tiles
+    fn get_distance(&self, from: &(i32, i32), to: &(i32, i32)) -> i32 {
+        let (w, h) = ((from.0 - to.0).abs(), (from.1 - to.1).abs());
+        return match self {
+            Connectivity::Four => w + h,
+            Connectivity::Eight => w.max(h),
+        };
+    }
+}
+
+/// How much it costs for units to enter a tile.
+///
+/// The cost of a tile is the highest cost of the Kinds on its layers (Kinds
+/// that are not listed do not slow units down), or none at all if one of them
+/// is impassable. Land units and sailing units each have their own costs,
+/// while whether a unit can go on a tile at all is up to `unit::can_enter`.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct MovementCosts {
+    /// Kinds mapped to None are impassable
+    pub kinds: HashMap<Kind, Option<f32>>,
+    /// Same as `kinds`, for units that sail
+    pub sea_kinds: HashMap<Kind, Option<f32>>,
+    /// Multiplier applied when moving along a road (from a road tile to
+    /// another)
+    pub road_factor: f32,
+}
+
+impl Default for MovementCosts {
+    fn default() -> Self {
+        return MovementCosts {
+            kinds: HashMap::from([
+                (Kind::TKind(TerrainKind::Plain), Some(1.)),
+                (Kind::TKind(TerrainKind::Desert), Some(1.)),
+                (Kind::TKind(TerrainKind::Grassland), Some(1.)),
+                (Kind::TKind(TerrainKind::Savanna), Some(1.)),
+                (Kind::TKind(TerrainKind::Tundra), Some(1.)),
+                (Kind::TKind(TerrainKind::Snow), Some(2.)),
+                (Kind::TKind(TerrainKind::Jungle), Some(2.)),
+                (Kind::TKind(TerrainKind::Swamp), Some(2.)),
+                (Kind::FKind(FeatureKind::Forest), Some(2.)),
+                (Kind::FKind(FeatureKind::Hill), Some(2.)),
+                (Kind::FKind(FeatureKind::River), Some(2.)),
+                (Kind::SKind(SpecialKind::Mountain), Some(3.)),
+            ]),
+            sea_kinds: HashMap::from([
+                (Kind::FKind(FeatureKind::Lake), Some(1.)),
+                (Kind::FKind(FeatureKind::Ocean), Some(1.)),
+                (Kind::FKind(FeatureKind::DeepOcean), Some(1.)),
+            ]),
+            road_factor: 0.5,
+        };
+    }
+}
+
+impl MovementCosts {
+    /// Cost for a unit to enter a tile, if it can
+    pub fn get_tile_cost(&self, tile: &Tile, unit_kind: &UnitKind) -> Option<f32> {
+        if !can_enter(tile, unit_kind) {
+            return None;
+        }
+        let kinds = if sails(unit_kind) {
+            &self.sea_kinds
+        } else {
+            &self.kinds
+        };
+
+        let mut cost: f32 = 1.;
+        for kind in tile.layers.values() {
+            match kinds.get(kind) {
+                Some(Some(kind_cost)) => cost = cost.max(*kind_cost),
+                Some(None) => return None,
+                None => {}
+            }
+        }
+        return Some(cost);
+    }
+
+    /// Cost for a unit to move from a tile to one of its neighbours, if it can
+    pub fn get_step_cost(
+        &self,
+        map: &Map,
+        from: &(i32, i32),
+        to: &(i32, i32),
+        unit_kind: &UnitKind,
+    ) -> Option<f32> {
+        let cost = self.get_tile_cost(map.get(to)?, unit_kind)?;
+        let has_road = |coordinates: &(i32, i32)| {
+            map.get(coordinates)
+                .and_then(|tile| tile.layers.get(&Layer::Development))
+                == Some(&Kind::DKind(DevelopmentKind::Road))
+        };
+        if has_road(from) && has_road(to) {
+            return Some(cost * self.road_factor);
+        }
+        return Some(cost);
+    }
+
+    /// The cheapest any step can be, so that the search never overestimates
+    /// the remaining cost
+    fn get_lowest_step_cost(&self) -> f32 {
+        let lowest_tile_cost = self
+            .kinds
+            .values()
+            .chain(self.sea_kinds.values())
+            .flatten()
+            .fold(1_f32, |lowest, cost| lowest.min(*cost));
+        return lowest_tile_cost * self.road_factor.min(1.);
+    }
+}
+
+/// A way from a tile to another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    /// Every tile to go through, from the start to the goal (both included)
+    pub steps: Vec<(i32, i32)>,
+    pub cost: f32,
+}
+
+/// A tile waiting to be explored, ordered so that the one with the lowest
+/// estimated total cost comes first out of the BinaryHeap
+struct Candidate {
+    estimated_cost: f32,
+    coordinates: (i32, i32),
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Coordinates break ties, so that the same path is always found
+        return other
+            .estimated_cost
+            .total_cmp(&self.estimated_cost)
+            .then_with(|| other.coordinates.cmp(&self.coordinates));
+    }
+}
+
+/// Cheapest known cost to reach a tile, along with where it was reached from
+type BestCosts = HashMap<(i32, i32), (f32, Option<(i32, i32)>)>;
+
+/// Find the cheapest path for a unit from a tile to another (with A*), if
+/// there is one.
+pub fn find_path(
+    map: &Map,
+    start: &(i32, i32),
+    goal: &(i32, i32),
+    unit_kind: &UnitKind,
+    costs: &MovementCosts,
+    connectivity: Connectivity,
+) -> Option<Path> {
+    costs.get_tile_cost(map.get(start)?, unit_kind)?;
+    costs.get_tile_cost(map.get(goal)?, unit_kind)?;
+
+    let lowest_step_cost = costs.get_lowest_step_cost();
+    let estimate = |coordinates: &(i32, i32)| {
+        return connectivity.get_distance(coordinates, goal) as f32 * lowest_step_cost;
+    };
+
+    let mut best: BestCosts = HashMap::new();
+    let mut candidates = BinaryHeap::new();
+    best.insert(*start, (0., None));
+    candidates.push(Candidate {
+        estimated_cost: estimate(start),
+        coordinates: *start,
+    });
+
+    while let Some(Candidate {
+        estimated_cost,
+        coordinates,
+    }) = candidates.pop()
+    {
+        let cost = best[&coordinates].0;
+        // A cheaper way to this tile was found after it was queued
+        if estimated_cost > cost + estimate(&coordinates) {
+            continue;
+        }
+        if coordinates == *goal {
+            let mut steps = vec![coordinates];
+            while let Some(previous) = best[steps.last().unwrap()].1 {
+                steps.push(previous);
+            }
+            steps.reverse();
+            return Some(Path { steps, cost });
+        }
+
+        for neighbour in connectivity.get_neighbours() {
+            let offset = neighbour.get_offset();
+            let next = (coordinates.0 + offset.0, coordinates.1 + offset.1);
+            let Some(step_cost) = costs.get_step_cost(map, &coordinates, &next, unit_kind) else {
+                continue;
+            };
+            let next_cost = cost + step_cost;
+            let is_cheaper = match best.get(&next) {
+                Some((known_cost, _)) => next_cost < *known_cost,
+                None => true,
+            };
+            if is_cheaper {
+                best.insert(next, (next_cost, Some(coordinates)));
+                candidates.push(Candidate {
+                    estimated_cost: next_cost + estimate(&next),
+                    coordinates: next,
+                });
+            }
+        }
+    }
+
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::{tests::draw_map, water::is_deep_water};
+
+    fn find(map: &Map, start: (i32, i32), goal: (i32, i32), unit_kind: UnitKind) -> Option<Path> {
+        return find_path(
+            map,
+            &start,
+            &goal,
+            &unit_kind,
+            &MovementCosts::default(),
+            Connectivity::Eight,
+        );
+    }
+
+    #[test]
+    fn diagonals_only_with_eight_connectivity() {
+        let map = draw_map(&["...", "...", "..."]);
+        let costs = MovementCosts::default();
+        let four = find_path(
+            &map,
+            &(0, 0),
+            &(2, 2),
+            &UnitKind::Settler,
+            &costs,
+            Connectivity::Four,
+        )
+        .unwrap();
+        assert_eq!(four.steps.len(), 5);
+        assert_eq!(four.cost, 4.);
+        let eight = find(&map, (0, 0), (2, 2), UnitKind::Settler).unwrap();
+        assert_eq!(eight.steps, vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(eight.cost, 2.);
+    }
+
+    #[test]
+    fn forests_and_hills_cost_two() {
+        let map = draw_map(&[".f.h"]);
+        assert_eq!(
+            find(&map, (0, 0), (1, 0), UnitKind::Worker).unwrap().cost,
+            2.
+        );
+        assert_eq!(
+            find(&map, (2, 0), (3, 0), UnitKind::Worker).unwrap().cost,
+            2.
+        );
+        assert_eq!(
+            find(&map, (1, 0), (2, 0), UnitKind::Worker).unwrap().cost,
+            1.
+        );
+    }
+
+    #[test]
+    fn mountains_cost_three_unless_impassable() {
+        let map = draw_map(&[".m."]);
+        let path = find(&map, (0, 0), (2, 0), UnitKind::Soldier).unwrap();
+        assert_eq!(path.steps, vec![(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(path.cost, 4.);
+
+        let mut costs = MovementCosts::default();
+        costs.kinds.insert(Kind::SKind(SpecialKind::Mountain), None);
+        assert_eq!(costs.get_tile_cost(&map[&(1, 0)], &UnitKind::Soldier), None);
+        assert_eq!(
+            find_path(
+                &map,
+                &(0, 0),
+                &(2, 0),
+                &UnitKind::Soldier,
+                &costs,
+                Connectivity::Eight
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn land_units_go_around_the_sea() {
+        let map = draw_map(&["...", ".~.", "..."]);
+        let path = find(&map, (0, 1), (2, 1), UnitKind::Cannon).unwrap();
+        assert!(!path.steps.contains(&(1, 1)));
+        assert_eq!(path.cost, 2.);
+        assert_eq!(find(&map, (0, 1), (1, 1), UnitKind::Cannon), None);
+        // Boats are the other way round
+        assert_eq!(find(&map, (1, 1), (0, 1), UnitKind::Boat), None);
+    }
+
+    #[test]
+    fn only_ships_cross_the_deep_ocean() {
+        let map = draw_map(&["~~~~~", "~ddd~", "~ddd~", "~ddd~", "~~~~~"]);
+        assert_eq!(find(&map, (0, 2), (2, 2), UnitKind::Boat), None);
+        let path = find(&map, (0, 2), (4, 2), UnitKind::Boat).unwrap();
+        assert!(path.steps.iter().all(|step| !is_deep_water(&map[step])));
+        assert_eq!(path.cost, 6.);
+        assert_eq!(find(&map, (0, 2), (4, 2), UnitKind::Ship).unwrap().cost, 4.);
+
+        // Sea costs apply to ships as they do to boats
+        let mut costs = MovementCosts::default();
+        costs
+            .sea_kinds
+            .insert(Kind::FKind(FeatureKind::DeepOcean), Some(3.));
+        let path = find_path(
+            &map,
+            &(0, 2),
+            &(4, 2),
+            &UnitKind::Ship,
+            &costs,
+            Connectivity::Eight,
+        )
+        .unwrap();
+        assert_eq!(path.cost, 6.);
+    }
+
+    #[test]
+    fn roads_halve_the_cost() {
+        let map = draw_map(&["===f", "...f"]);
+        assert_eq!(
+            find(&map, (0, 1), (2, 1), UnitKind::Settler).unwrap().cost,
+            1.
+        );
+        assert_eq!(
+            find(&map, (0, 0), (2, 0), UnitKind::Settler).unwrap().cost,
+            2.
+        );
+        // Only when moving from a road to another
+        assert_eq!(
+            find(&map, (2, 1), (3, 1), UnitKind::Settler).unwrap().cost,
+            2.
+        );
+    }
+
+    #[test]
+    fn unreachable_targets_have_no_path() {
+        let map = draw_map(&["..~.."]);
+        assert_eq!(find(&map, (0, 0), (4, 0), UnitKind::Settler), None);
+        assert_eq!(find(&map, (0, 0), (9, 9), UnitKind::Settler), None);
+        assert_eq!(
+            find(&map, (0, 0), (0, 0), UnitKind::Settler),
+            Some(Path {
+                steps: vec![(0, 0)],
+                cost: 0.
+            })
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Read-only questions about the Map.
+//!
+//! Gameplay systems only see the Map through these, rather than walking its
+//! HashMap by hand, so that they all agree on what «around» or «between» means.
+
+use super::{autotile::Neighbour, get_layer_from_kind, Kind, Map, Tile};
+
+/// Questions gameplay systems keep asking the Map.
+///
+/// Tiles are always returned along with their coordinates, in a stable order
+/// (column by column) so that callers stay deterministic.
+pub trait MapQuery {
+    /// The tile at the given coordinates, if they are inside the map
+    fn tile_at(&self, coordinates: &(i32, i32)) -> Option<&Tile>;
+
+    /// The (up to 8) tiles around the given coordinates
+    fn neighbours(&self, coordinates: &(i32, i32)) -> Vec<((i32, i32), &Tile)>;
+
+    /// The tiles between two corners (both included)
+    fn tiles_in_rect(&self, corner: &(i32, i32), opposite: &(i32, i32))
+        -> Vec<((i32, i32), &Tile)>;
+
+    /// The tiles holding a Kind on its layer
+    fn tiles_with_kind(&self, kind: &Kind) -> Vec<((i32, i32), &Tile)>;
+}
+
+impl MapQuery for Map {
+    fn tile_at(&self, coordinates: &(i32, i32)) -> Option<&Tile> {
+        return self.get(coordinates);
+    }
+
+    fn neighbours(&self, coordinates: &(i32, i32)) -> Vec<((i32, i32), &Tile)> {
+        let mut offsets: Vec<(i32, i32)> = Neighbour::ALL
+            .iter()
+            .map(|neighbour| neighbour.get_offset())
+            .collect();
+        offsets.sort();
+        return offsets
+            .iter()
+            .filter_map(|offset| {
+                let neighbour = (coordinates.0 + offset.0, coordinates.1 + offset.1);
+                Some((neighbour, self.get(&neighbour)?))
+            })
+            .collect();
+    }
+
+    fn tiles_in_rect(
+        &self,
+        corner: &(i32, i32),
+        opposite: &(i32, i32),
+    ) -> Vec<((i32, i32), &Tile)> {
+        let mut tiles = Vec::new();
+        for w in corner.0.min(opposite.0)..=corner.0.max(opposite.0) {
+            for h in corner.1.min(opposite.1)..=corner.1.max(opposite.1) {
+                if let Some(tile) = self.get(&(w, h)) {
+                    tiles.push(((w, h), tile));
+                }
+            }
+        }
+        return tiles;
+    }
+
+    fn tiles_with_kind(&self, kind: &Kind) -> Vec<((i32, i32), &Tile)> {
+        let layer = get_layer_from_kind(kind);
+        let mut tiles: Vec<((i32, i32), &Tile)> = self
+            .iter()
+            .filter(|(_, tile)| tile.layers.get(&layer) == Some(kind))
+            .map(|(coordinates, tile)| (*coordinates, tile))
+            .collect();
+        tiles.sort_by_key(|(coordinates, _)| *coordinates);
+        return tiles;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::{tests::draw_map, FeatureKind};
+
+    /// Coordinates of the tiles a query returned
+    fn get_coordinates(tiles: Vec<((i32, i32), &Tile)>) -> Vec<(i32, i32)> {
+        return tiles
+            .into_iter()
+            .map(|(coordinates, _)| coordinates)
+            .collect();
+    }
+
+    #[test]
+    fn tile_at_stays_inside_the_map() {
+        let map = draw_map(&["..", ".~"]);
+        assert!(map.tile_at(&(1, 0)).is_some());
+        assert!(map.tile_at(&(2, 0)).is_none());
+        assert!(map.tile_at(&(-1, 0)).is_none());
+    }
+
+    #[test]
+    fn neighbours_are_cut_at_the_edges() {
+        let map = draw_map(&["...", "...", "..."]);
+        assert_eq!(map.neighbours(&(1, 1)).len(), 8);
+        assert_eq!(
+            get_coordinates(map.neighbours(&(0, 0))),
+            vec![(0, 1), (1, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn rect_corners_in_any_order() {
+        let map = draw_map(&["....", "....", "...."]);
+        let expected = vec![(1, 0), (1, 1), (2, 0), (2, 1)];
+        assert_eq!(
+            get_coordinates(map.tiles_in_rect(&(1, 0), &(2, 1))),
+            expected
+        );
+        assert_eq!(
+            get_coordinates(map.tiles_in_rect(&(2, 1), &(1, 0))),
+            expected
+        );
+        assert_eq!(
+            get_coordinates(map.tiles_in_rect(&(1, 1), &(2, 0))),
+            expected
+        );
+        assert_eq!(
+            get_coordinates(map.tiles_in_rect(&(2, 2), &(2, 2))),
+            vec![(2, 2)]
+        );
+    }
+
+    #[test]
+    fn rect_is_cut_at_the_edges() {
+        let map = draw_map(&["...", "..."]);
+        assert_eq!(
+            get_coordinates(map.tiles_in_rect(&(-5, -5), &(0, 0))),
+            vec![(0, 0)]
+        );
+        assert_eq!(map.tiles_in_rect(&(-5, 0), &(9, 9)).len(), 6);
+        assert!(map.tiles_in_rect(&(10, 10), &(20, 20)).is_empty());
+    }
+
+    #[test]
+    fn tiles_with_kind_in_stable_order() {
+        let map = draw_map(&["~.~", ".f.", "~.."]);
+        assert_eq!(
+            get_coordinates(map.tiles_with_kind(&Kind::FKind(FeatureKind::Ocean))),
+            vec![(0, 0), (0, 2), (2, 2)]
+        );
+        assert_eq!(
+            get_coordinates(map.tiles_with_kind(&Kind::FKind(FeatureKind::Forest))),
+            vec![(1, 1)]
+        );
+        assert!(map
+            .tiles_with_kind(&Kind::FKind(FeatureKind::Hill))
+            .is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+//! Rivers, flowing down from the highlands to the sea.
+//!
+//! Every river springs from a hill and follows the steepest slope of the
+//! elevation, one side of a tile at a time (so that rivers connect like roads
+//! do), until it meets the sea, a lake or another river. A river stuck in a
+//! hollow fills it with a lake, then flows on from wherever the lake spills
+//! over. Lakes never touch the sea, not even by a corner: water reaching it
+//! flows into it instead.
+
+use bevy::utils::HashSet;
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+use super::{
+    config::MapGenConfig, query::MapQuery, water::is_water, FeatureKind, Kind, Layer, Map,
+};
+
+/// Neighbours a river can flow to: top, left, right, bottom
+const CARDINAL_OFFSETS: [(i32, i32); 4] = [(0, 1), (-1, 0), (1, 0), (0, -1)];
+
+/// Where a river flows, before it is laid on the map
+#[derive(Default)]
+struct RiverCourse {
+    river: Vec<(i32, i32)>,
+    lakes: Vec<(i32, i32)>,
+}
+
+/// The neighbours of a tile a river can flow to (missing ones are outside of
+/// the map)
+fn get_cardinal_neighbours(map: &Map, coordinates: &(i32, i32)) -> Vec<(i32, i32)> {
+    return CARDINAL_OFFSETS
+        .iter()
+        .map(|offset| (coordinates.0 + offset.0, coordinates.1 + offset.1))
+        .filter(|neighbour| map.contains_key(neighbour))
+        .collect();
+}
+
+fn has_feature(map: &Map, coordinates: &(i32, i32), feature: FeatureKind) -> bool {
+    return map
+        .get(coordinates)
+        .and_then(|tile| tile.layers.get(&Layer::Feature))
+        == Some(&Kind::FKind(feature));
+}
+
+/// Whether a river reaching this tile has reached its end
+fn is_river_end(map: &Map, coordinates: &(i32, i32)) -> bool {
+    return map.get(coordinates).is_some_and(is_water)
+        || has_feature(map, coordinates, FeatureKind::River);
+}
+
+/// Whether a tile is next to the sea, diagonals included
+fn touches_sea(map: &Map, coordinates: &(i32, i32)) -> bool {
+    return map.neighbours(coordinates).iter().any(|(_, tile)| {
+        matches!(
+            tile.layers.get(&Layer::Feature),
+            Some(Kind::FKind(FeatureKind::Ocean | FeatureKind::DeepOcean))
+        )
+    });
+}
+
+fn get_height(map: &Map, coordinates: &(i32, i32)) -> f32 {
+    return map
+        .get(coordinates)
+        .map_or(f32::MAX, |tile| tile.climate.elevation);
+}
+
+/// Fill the hollow around a tile with a lake, the way water would: the lowest
+/// tile of its shore is flooded first, until the water finds a way further
+/// down (which is returned) or the lake reaches its maximum size.
+fn fill_lake(
+    map: &Map,
+    hollow: (i32, i32),
+    max_size: usize,
+    visited: &HashSet<(i32, i32)>,
+    lake: &mut Vec<(i32, i32)>,
+) -> Option<(i32, i32)> {
+    lake.push(hollow);
+    let mut shore: Vec<(i32, i32)> = get_cardinal_neighbours(map, &hollow);
+
+    while lake.len() < max_size {
+        // The shore is a Vec (rather than a set) so that ties between tiles of
+        // the same height always break the same way
+        let (index, lowest) = shore
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, coordinates)| !lake.contains(coordinates))
+            .min_by(|(_, a), (_, b)| get_height(map, a).total_cmp(&get_height(map, b)))?;
+        shore.swap_remove(index);
+
+        let level = get_height(map, &lowest);
+        let spills = is_river_end(map, &lowest)
+            || touches_sea(map, &lowest)
+            || get_cardinal_neighbours(map, &lowest)
+                .iter()
+                .any(|neighbour| {
+                    !lake.contains(neighbour)
+                        && !visited.contains(neighbour)
+                        && get_height(map, neighbour) < level
+                });
+        if spills {
+            return Some(lowest);
+        }
+
+        lake.push(lowest);
+        for neighbour in get_cardinal_neighbours(map, &lowest) {
+            if !lake.contains(&neighbour) && !shore.contains(&neighbour) {
+                shore.push(neighbour);
+            }
+        }
+    }
+    return None;
+}
+
+/// Follow the slope from a source down to the sea, another river, or a lake
+/// that cannot spill over
+fn trace_river(map: &Map, source: (i32, i32), max_lake_size: usize) -> RiverCourse {
+    let mut course = RiverCourse::default();
+    let mut visited: HashSet<(i32, i32)> = HashSet::new();
+    let mut current = source;
+
+    loop {
+        visited.insert(current);
+        // Rivers only show once they leave the hills
+        let is_highland = map
+            .get(&current)
+            .unwrap()
+            .layers
+            .contains_key(&Layer::Feature);
+        if !is_highland {
+            course.river.push(current);
+        }
+
+        // The lakes of the river itself do not end it, it flows out of them
+        let neighbours = get_cardinal_neighbours(map, &current);
+        if touches_sea(map, &current)
+            || neighbours
+                .iter()
+                .any(|neighbour| is_river_end(map, neighbour) && !course.lakes.contains(neighbour))
+        {
+            return course;
+        }
+
+        let next = neighbours
+            .iter()
+            .copied()
+            .filter(|neighbour| !visited.contains(neighbour))
+            .min_by(|a, b| get_height(map, a).total_cmp(&get_height(map, b)));
+        if let Some(next) = next {
+            if get_height(map, &next) < get_height(map, &current) {
+                current = next;
+                continue;
+            }
+        }
+
+        // Nowhere to go but up, the river pools into a lake
+        if course.river.last() == Some(&current) {
+            course.river.pop();
+        }
+        let mut lake = Vec::new();
+        let outlet = fill_lake(map, current, max_lake_size, &visited, &mut lake);
+        visited.extend(lake.iter().copied());
+        course.lakes.extend(lake);
+        match outlet {
+            Some(outlet) if !is_river_end(map, &outlet) => current = outlet,
+            _ => return course,
+        }
+    }
+}
+
+/// Trace rivers from random hills, and lay the long enough ones on the map
+/// (along with their lakes).
+///
+/// Sources are picked among every hill, so rivers mostly spring from the
+/// largest highlands. Many of them quickly join an existing river, and are
+/// dropped for being too short.
+pub fn place_rivers(pseudo_rng_instance: &mut StdRng, map: &mut Map, config: &MapGenConfig) {
+    let settings = &config.rivers;
+
+    // Sources are listed in a stable order so that the same seed always gives
+    // the same rivers
+    let mut sources: Vec<(i32, i32)> = Vec::new();
+    for w in 0..=config.width {
+        for h in 0..=config.height {
+            if has_feature(map, &(w, h), FeatureKind::Hill) {
+                sources.push((w, h));
+            }
+        }
+    }
+    sources.shuffle(pseudo_rng_instance);
+
+    let mut count = 0;
+    for source in sources {
+        if count >= settings.count {
+            break;
+        }
+        // Sources flooded by a previous lake
+        if !has_feature(map, &source, FeatureKind::Hill) {
+            continue;
+        }
+
+        let course = trace_river(map, source, settings.max_lake_size);
+        // Lakes may have flooded the river upstream of them
+        let river: Vec<(i32, i32)> = course
+            .river
+            .into_iter()
+            .filter(|coordinates| !course.lakes.contains(coordinates))
+            .collect();
+        if river.len() < settings.min_length {
+            continue;
+        }
+
+        for coordinates in course.lakes {
+            let layers = &mut map.get_mut(&coordinates).unwrap().layers;
+            layers.insert(Layer::Feature, Kind::FKind(FeatureKind::Lake));
+            layers.remove(&Layer::Special);
+        }
+        for coordinates in river {
+            map.get_mut(&coordinates)
+                .unwrap()
+                .layers
+                .insert(Layer::Feature, Kind::FKind(FeatureKind::River));
+        }
+        count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::tests::draw_map;
+
+    /// Set the elevation of every tile from rows of digits, laid out like
+    /// `draw_map`
+    fn set_heights(map: &mut Map, rows: &[&str]) {
+        for (row, line) in rows.iter().enumerate() {
+            let h = (rows.len() - 1 - row) as i32;
+            for (w, digit) in line.chars().enumerate() {
+                map.get_mut(&(w as i32, h)).unwrap().climate.elevation =
+                    digit.to_digit(10).unwrap() as f32;
+            }
+        }
+    }
+
+    #[test]
+    fn rivers_end_at_the_sea() {
+        let mut map = draw_map(&["h...~"]);
+        set_heights(&mut map, &["98760"]);
+        let course = trace_river(&map, (0, 0), 4);
+        assert_eq!(course.river, vec![(1, 0), (2, 0), (3, 0)]);
+        assert!(course.lakes.is_empty());
+        assert!(has_feature(&map, &(4, 0), FeatureKind::Ocean));
+    }
+
+    #[test]
+    fn rivers_end_in_lakes_that_cannot_spill_over() {
+        let mut map = draw_map(&["h.."]);
+        set_heights(&mut map, &["913"]);
+        let course = trace_river(&map, (0, 0), 2);
+        assert!(course.river.is_empty());
+        assert_eq!(course.lakes, vec![(1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn rivers_never_run_uphill() {
+        let mut map = draw_map(&["h....", ".....", "...~~"]);
+        set_heights(&mut map, &["98765", "87654", "76500"]);
+        let course = trace_river(&map, (0, 2), 4);
+        assert!(course.lakes.is_empty());
+        assert!(!course.river.is_empty());
+        for step in course.river.windows(2) {
+            assert!(
+                get_height(&map, &step[1]) < get_height(&map, &step[0]),
+                "{:?}",
+                step
+            );
+        }
+        let mouth = course.river.last().unwrap();
+        assert!(get_cardinal_neighbours(&map, mouth)
+            .iter()
+            .any(|neighbour| has_feature(&map, neighbour, FeatureKind::Ocean)));
+    }
+
+    #[test]
+    fn lakes_stop_at_the_spill_height() {
+        let mut map = draw_map(&["......."]);
+        set_heights(&mut map, &["9512430"]);
+        let mut lake = Vec::new();
+        let outlet = fill_lake(&map, (2, 0), 10, &HashSet::new(), &mut lake);
+
+        // The water rises up to the tile at height 4, and flows out of it
+        // towards the one at height 3, without flooding the one at height 5
+        assert_eq!(outlet, Some((4, 0)));
+        assert_eq!(lake, vec![(2, 0), (3, 0)]);
+        assert!(lake
+            .iter()
+            .all(|tile| get_height(&map, tile) < get_height(&map, &(4, 0))));
+    }
+}
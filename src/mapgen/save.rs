@@ -0,0 +1,802 @@
+//! Saving and loading maps.
+//!
+//! Maps are saved along with the seed and configuration that generated them,
+//! in one of two formats:
+//! - RON, for humans: every tile lists its Kinds, column by column, then its
+//!   climate
+//! - binary, for large maps: a RON header (with a palette of every Kind used)
+//!   followed by one byte per tile layer, then by the climate of every tile
+//!   (three little-endian f32)
+//!
+//! Both start with a format version. Older saves are upgraded by migrations
+//! rewriting their RON text (the whole file, or the header of binary saves)
+//! before it gets parsed, so that they keep loading as Kinds get renamed, then
+//! completing the loaded map with what the older format did not hold.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    climate::{get_climate, Climate},
+    config::{MapGenConfig, MapGenConfigError},
+    get_layer_from_kind,
+    water::{is_deep_water, label_water_bodies, place_deep_ocean, place_lakes},
+    FeatureKind, Kind, Layer, Map, MapSeed, SpecialKind, Tile, TileLayers, SPRITE_SIZE,
+};
+
+/// First bytes of every binary save
+const BINARY_MAGIC: &[u8; 8] = b"SPRTSIM\0";
+
+/// An upgrade of saves from a version to the next one
+struct Migration {
+    /// Rewrite of the RON text, before it gets parsed
+    text: fn(String) -> String,
+    /// Completion of the loaded map
+    map: fn(&mut Map, &MapSeed, &MapGenConfig),
+}
+
+/// Upgrades of older saves: the first one turns a version 1 save into a
+/// version 2 one, and so on.
+const MIGRATIONS: [Migration; 4] = [
+    // Version 2 saves the climate of every tile
+    Migration {
+        text: keep_text,
+        map: redraw_climate,
+    },
+    // Version 3 configurations have biomes instead of a desert band
+    Migration {
+        text: remove_desert_band,
+        map: keep_map,
+    },
+    // Version 4 tells lakes apart from the ocean
+    Migration {
+        text: keep_text,
+        map: place_saved_lakes,
+    },
+    // Version 5 tells the deep ocean apart from coastal waters
+    Migration {
+        text: keep_text,
+        map: place_saved_deep_ocean,
+    },
+];
+
+/// Version of the saves written by this build
+pub const SAVE_VERSION: u32 = 1 + MIGRATIONS.len() as u32;
+
+/// How a map is written on disk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveFormat {
+    Ron,
+    Binary,
+}
+
+impl SaveFormat {
+    /// RON for `.ron` files, binary for anything else
+    pub fn from_path(path: &Path) -> Self {
+        return match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => SaveFormat::Ron,
+            _ => SaveFormat::Binary,
+        };
+    }
+}
+
+/// A map along with where it came from
+#[derive(Debug)]
+pub struct SavedMap {
+    pub seed: MapSeed,
+    pub config: MapGenConfig,
+    pub map: Map,
+}
+
+/// Everything that can go wrong when saving or loading a map
+#[derive(Debug)]
+pub enum SaveError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Serialize(PathBuf, ron::Error),
+    /// The save was written by a more recent build
+    UnsupportedVersion(PathBuf, u32),
+    /// The generation configuration of the save cannot produce a map
+    InvalidConfig(PathBuf, Box<MapGenConfigError>),
+    Corrupted(PathBuf, String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            SaveError::Io(path, error) => {
+                write!(f, "Cannot access «{}»: {}", path.display(), error)
+            }
+            SaveError::Parse(path, error) => {
+                write!(f, "Cannot parse «{}»: {}", path.display(), error)
+            }
+            SaveError::Serialize(path, error) => {
+                write!(f, "Cannot write «{}»: {}", path.display(), error)
+            }
+            SaveError::UnsupportedVersion(path, version) => write!(
+                f,
+                "«{}» is a version {} save, but only versions up to {} are supported",
+                path.display(),
+                version,
+                SAVE_VERSION
+            ),
+            SaveError::InvalidConfig(path, error) => {
+                write!(f, "«{}» cannot be loaded: {}", path.display(), error)
+            }
+            SaveError::Corrupted(path, reason) => {
+                write!(f, "«{}» is corrupted: {}", path.display(), reason)
+            }
+        };
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Just enough of a RON save to know its version
+#[derive(Deserialize)]
+struct RonVersion {
+    version: u32,
+}
+
+/// A RON save
+#[derive(Serialize, Deserialize)]
+struct RonSave {
+    version: u32,
+    seed: u64,
+    width: i32,
+    height: i32,
+    config: MapGenConfig,
+    /// The Kinds of every tile, column by column
+    tiles: Vec<Vec<Kind>>,
+    /// The climate of every tile, in the same order
+    #[serde(default)]
+    climates: Vec<Climate>,
+}
+
+/// The header of a binary save
+#[derive(Serialize, Deserialize)]
+struct BinaryHeader {
+    seed: u64,
+    width: i32,
+    height: i32,
+    config: MapGenConfig,
+    /// Every Kind used in the map, tiles refer to them by index
+    kinds: Vec<Kind>,
+}
+
+/// Coordinates of every tile, in the order they are saved
+fn get_tile_coordinates(width: i32, height: i32) -> impl Iterator<Item = (i32, i32)> {
+    return (0..=width).flat_map(move |w| (0..=height).map(move |h| (w, h)));
+}
+
+/// Size (in bytes) of the climate of a tile in binary saves
+const BINARY_CLIMATE_SIZE: usize = 12;
+
+/// The climate of a tile, or the default one if it is not in the map
+fn get_tile_climate(map: &Map, coordinates: &(i32, i32)) -> Climate {
+    return map
+        .get(coordinates)
+        .map(|tile| tile.climate)
+        .unwrap_or_default();
+}
+
+/// The Kinds of a tile, from the bottom layer to the top one
+fn get_tile_kinds(map: &Map, coordinates: &(i32, i32)) -> Vec<Kind> {
+    return match map.get(coordinates) {
+        Some(tile) => Layer::ALL
+            .iter()
+            .filter_map(|layer| tile.layers.get(layer).copied())
+            .collect(),
+        None => Vec::new(),
+    };
+}
+
+fn build_tile(coordinates: &(i32, i32), kinds: &[Kind], climate: Climate) -> Tile {
+    let layers: TileLayers = kinds
+        .iter()
+        .map(|kind| (get_layer_from_kind(kind), *kind))
+        .collect();
+    return Tile {
+        layers,
+        real_coordinates: (
+            coordinates.0 as f32 * SPRITE_SIZE,
+            coordinates.1 as f32 * SPRITE_SIZE,
+        ),
+        climate,
+        water_body: None,
+    };
+}
+
+fn keep_text(content: String) -> String {
+    return content;
+}
+
+fn keep_map(_map: &mut Map, _seed: &MapSeed, _config: &MapGenConfig) {}
+
+/// Draw the climate of every tile again from the seed, for saves that did not
+/// hold it
+fn redraw_climate(map: &mut Map, seed: &MapSeed, config: &MapGenConfig) {
+    let climate = get_climate(seed, config);
+    for (coordinates, tile) in map.iter_mut() {
+        tile.climate = climate.get(coordinates).copied().unwrap_or_default();
+    }
+}
+
+/// Turn into lakes the seas of saves that only knew the ocean, along with the
+/// fish living in them
+fn place_saved_lakes(map: &mut Map, _seed: &MapSeed, config: &MapGenConfig) {
+    place_lakes(map, config);
+    for tile in map.values_mut() {
+        if tile.layers.get(&Layer::Feature) == Some(&Kind::FKind(FeatureKind::Lake))
+            && tile.layers.get(&Layer::Special) == Some(&Kind::SKind(SpecialKind::Fish))
+        {
+            tile.layers
+                .insert(Layer::Special, Kind::SKind(SpecialKind::FreshwaterFish));
+        }
+    }
+}
+
+/// Turn into deep ocean the open sea of saves that only knew coastal waters,
+/// where fish do not live
+fn place_saved_deep_ocean(map: &mut Map, _seed: &MapSeed, config: &MapGenConfig) {
+    place_deep_ocean(map, config);
+    for tile in map.values_mut() {
+        if is_deep_water(tile) {
+            tile.layers.remove(&Layer::Special);
+        }
+    }
+}
+
+/// Remove a field (along with its value) from RON text, wherever it is
+fn remove_ron_field(content: String, name: &str) -> String {
+    let field = format!("{}:", name);
+    let Some(field_start) = content.find(&field) else {
+        return content;
+    };
+    let value_start = field_start + field.len();
+
+    // The value ends on the first comma (or closing bracket) outside of it
+    let mut depth = 0;
+    let mut end = content.len();
+    for (offset, character) in content[value_start..].char_indices() {
+        match character {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => {
+                end = value_start + offset;
+                break;
+            }
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                end = value_start + offset + 1;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    // The whitespace before the field goes as well
+    let start = content[..field_start].trim_end().len();
+    return format!("{}{}", &content[..start], &content[end..]);
+}
+
+/// Deserts used to be laid out in a band, biomes now decide where they are
+fn remove_desert_band(content: String) -> String {
+    return remove_ron_field(content, "desert_band");
+}
+
+/// Apply the text migrations needed to bring a save to the current version
+fn migrate_text(path: &Path, version: u32, content: String) -> Result<String, SaveError> {
+    if version == 0 || version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(path.to_path_buf(), version));
+    }
+    return Ok(MIGRATIONS[(version - 1) as usize..]
+        .iter()
+        .fold(content, |content, migration| (migration.text)(content)));
+}
+
+/// Apply the map migrations needed to bring a save to the current version (its
+/// text must have been migrated already)
+fn migrate_map(version: u32, map: &mut Map, seed: &MapSeed, config: &MapGenConfig) {
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        (migration.map)(map, seed, config);
+    }
+}
+
+/// Write a map, in the format matching the file extension
+pub fn save_map(
+    path: PathBuf,
+    seed: &MapSeed,
+    config: &MapGenConfig,
+    map: &Map,
+) -> Result<(), SaveError> {
+    let (width, height) = (config.width, config.height);
+    let bytes = match SaveFormat::from_path(&path) {
+        SaveFormat::Ron => {
+            let save = RonSave {
+                version: SAVE_VERSION,
+                seed: seed.0,
+                width,
+                height,
+                config: config.clone(),
+                tiles: get_tile_coordinates(width, height)
+                    .map(|coordinates| get_tile_kinds(map, &coordinates))
+                    .collect(),
+                climates: get_tile_coordinates(width, height)
+                    .map(|coordinates| get_tile_climate(map, &coordinates))
+                    .collect(),
+            };
+            ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default().depth_limit(2))
+                .map_err(|error| SaveError::Serialize(path.clone(), error))?
+                .into_bytes()
+        }
+        SaveFormat::Binary => {
+            let mut kinds: Vec<Kind> = Vec::new();
+            let mut tiles: Vec<u8> = Vec::new();
+            for coordinates in get_tile_coordinates(width, height) {
+                let tile_kinds = get_tile_kinds(map, &coordinates);
+                tiles.push(tile_kinds.len() as u8);
+                for kind in tile_kinds {
+                    let index = match kinds.iter().position(|known| *known == kind) {
+                        Some(index) => index,
+                        None => {
+                            kinds.push(kind);
+                            kinds.len() - 1
+                        }
+                    };
+                    tiles.push(index as u8);
+                }
+            }
+            for coordinates in get_tile_coordinates(width, height) {
+                let climate = get_tile_climate(map, &coordinates);
+                for value in [climate.elevation, climate.moisture, climate.temperature] {
+                    tiles.extend(value.to_le_bytes());
+                }
+            }
+
+            let header = ron::to_string(&BinaryHeader {
+                seed: seed.0,
+                width,
+                height,
+                config: config.clone(),
+                kinds,
+            })
+            .map_err(|error| SaveError::Serialize(path.clone(), error))?;
+
+            let mut bytes = BINARY_MAGIC.to_vec();
+            bytes.extend(SAVE_VERSION.to_le_bytes());
+            bytes.extend((header.len() as u32).to_le_bytes());
+            bytes.extend(header.into_bytes());
+            bytes.extend(tiles);
+            bytes
+        }
+    };
+
+    return fs::write(&path, bytes).map_err(|error| SaveError::Io(path, error));
+}
+
+/// Read a map saved in any format, from any version
+pub fn load_map(path: PathBuf) -> Result<SavedMap, SaveError> {
+    let bytes = fs::read(&path).map_err(|error| SaveError::Io(path.clone(), error))?;
+    let corrupted = |reason: &str| SaveError::Corrupted(path.clone(), reason.to_string());
+
+    let (version, seed, config, tiles, climates) = if bytes.starts_with(BINARY_MAGIC) {
+        let read_u32 = |offset: usize| {
+            bytes
+                .get(offset..offset + 4)
+                .map(|slice| u32::from_le_bytes(slice.try_into().unwrap()))
+                .ok_or_else(|| corrupted("truncated header"))
+        };
+        let version = read_u32(BINARY_MAGIC.len())?;
+        let header_length = read_u32(BINARY_MAGIC.len() + 4)? as usize;
+        let header_start = BINARY_MAGIC.len() + 8;
+        let header_bytes = bytes
+            .get(header_start..header_start + header_length)
+            .ok_or_else(|| corrupted("truncated header"))?;
+        let header_text = String::from_utf8(header_bytes.to_vec())
+            .map_err(|_| corrupted("header is not valid text"))?;
+        let header: BinaryHeader = ron::from_str(&migrate_text(&path, version, header_text)?)
+            .map_err(|error| SaveError::Parse(path.clone(), error))?;
+
+        let mut data = bytes[header_start + header_length..].iter().copied();
+        let mut tiles: Vec<Vec<Kind>> = Vec::new();
+        for _ in get_tile_coordinates(header.width, header.height) {
+            let count = data.next().ok_or_else(|| corrupted("truncated tiles"))?;
+            let mut kinds = Vec::new();
+            for _ in 0..count {
+                let index = data.next().ok_or_else(|| corrupted("truncated tiles"))?;
+                kinds.push(
+                    *header
+                        .kinds
+                        .get(index as usize)
+                        .ok_or_else(|| corrupted("unknown Kind index"))?,
+                );
+            }
+            tiles.push(kinds);
+        }
+        let climate_bytes: Vec<u8> = data.collect();
+        if climate_bytes.len() % BINARY_CLIMATE_SIZE != 0 {
+            return Err(corrupted("truncated climates"));
+        }
+        let climates = climate_bytes
+            .chunks_exact(BINARY_CLIMATE_SIZE)
+            .map(|chunk| {
+                let read_f32 = |offset: usize| {
+                    f32::from_le_bytes(chunk[offset..offset + 4].try_into().unwrap())
+                };
+                Climate {
+                    elevation: read_f32(0),
+                    moisture: read_f32(4),
+                    temperature: read_f32(8),
+                }
+            })
+            .collect();
+        let mut config = header.config;
+        (config.width, config.height) = (header.width, header.height);
+        (version, header.seed, config, tiles, climates)
+    } else {
+        let content =
+            String::from_utf8(bytes).map_err(|_| corrupted("neither a binary nor a RON save"))?;
+        let version: RonVersion =
+            ron::from_str(&content).map_err(|error| SaveError::Parse(path.clone(), error))?;
+        let save: RonSave = ron::from_str(&migrate_text(&path, version.version, content)?)
+            .map_err(|error| SaveError::Parse(path.clone(), error))?;
+        let mut config = save.config;
+        (config.width, config.height) = (save.width, save.height);
+        (
+            version.version,
+            save.seed,
+            config,
+            save.tiles,
+            save.climates,
+        )
+    };
+
+    // A configuration that cannot produce a map is as good as corrupted
+    config
+        .validate()
+        .map_err(|error| SaveError::InvalidConfig(path.clone(), Box::new(error)))?;
+
+    let coordinates: Vec<(i32, i32)> = get_tile_coordinates(config.width, config.height).collect();
+    if coordinates.len() != tiles.len() {
+        return Err(corrupted(&format!(
+            "expected {} tiles, got {}",
+            coordinates.len(),
+            tiles.len()
+        )));
+    }
+    let seed = MapSeed(seed);
+    let climates = if version == 1 && climates.is_empty() {
+        // Drawn again by the migrations
+        vec![Climate::default(); coordinates.len()]
+    } else if climates.len() == coordinates.len() {
+        climates
+    } else {
+        return Err(corrupted(&format!(
+            "expected {} climates, got {}",
+            coordinates.len(),
+            climates.len()
+        )));
+    };
+    let mut map: Map = coordinates
+        .iter()
+        .zip(tiles.iter().zip(climates))
+        .map(|(coordinates, (kinds, climate))| {
+            (*coordinates, build_tile(coordinates, kinds, climate))
+        })
+        .collect::<HashMap<_, _>>();
+    migrate_map(version, &mut map, &seed, &config);
+    label_water_bodies(&mut map);
+
+    return Ok(SavedMap { seed, config, map });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::{build_map, tests::draw_map};
+
+    /// A small map, quick to build and save
+    fn small_config() -> MapGenConfig {
+        return MapGenConfig {
+            width: 24,
+            height: 16,
+            ..MapGenConfig::default()
+        };
+    }
+
+    /// Where a test saves its map (in a file of its own, since tests run in
+    /// parallel)
+    fn get_save_path(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("spritesim-save-{}", name));
+    }
+
+    /// Save and load a map again, ensuring nothing was lost on the way
+    fn check_round_trip(name: &str) {
+        let path = get_save_path(name);
+        let seed = MapSeed(42);
+        let config = small_config();
+        let mut map = build_map(&seed, &config);
+        // Edited tiles keep their climate, which no longer is the one the
+        // seed gives
+        map.get_mut(&(3, 5)).unwrap().climate = Climate {
+            elevation: -0.25,
+            moisture: 0.123,
+            temperature: 0.987,
+        };
+        save_map(path.clone(), &seed, &config, &map).unwrap();
+        let saved = load_map(path.clone()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(saved.seed, seed);
+        assert_eq!(saved.config, config);
+        assert_eq!(saved.map.len(), map.len());
+        for (coordinates, tile) in map.iter() {
+            let loaded = &saved.map[coordinates];
+            assert_eq!(loaded.layers, tile.layers);
+            assert_eq!(loaded.real_coordinates, tile.real_coordinates);
+            assert_eq!(loaded.climate, tile.climate);
+            assert_eq!(loaded.water_body, tile.water_body);
+        }
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        check_round_trip("round-trip.ron");
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        check_round_trip("round-trip.bin");
+    }
+
+    #[test]
+    fn version_1_saves_draw_their_climate_again() {
+        let path = get_save_path("version-1.bin");
+        let seed = MapSeed(7);
+        let config = small_config();
+        save_map(path.clone(), &seed, &config, &build_map(&seed, &config)).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let tile_count = get_tile_coordinates(config.width, config.height).count();
+        bytes.truncate(bytes.len() - tile_count * BINARY_CLIMATE_SIZE);
+        fs::write(&path, &bytes).unwrap();
+        // Only version 1 saves may lack climates
+        assert!(matches!(
+            load_map(path.clone()),
+            Err(SaveError::Corrupted(_, _))
+        ));
+
+        let offset = BINARY_MAGIC.len();
+        bytes[offset..offset + 4].copy_from_slice(&1u32.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        let saved = load_map(path.clone()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let climate = get_climate(&seed, &config);
+        for (coordinates, tile) in saved.map.iter() {
+            assert_eq!(tile.climate, climate[coordinates]);
+        }
+    }
+
+    /// Save a map, then rewrite its version
+    fn load_with_version(name: &str, version: u32) -> Result<SavedMap, SaveError> {
+        let path = get_save_path(name);
+        let seed = MapSeed(7);
+        let config = small_config();
+        save_map(path.clone(), &seed, &config, &build_map(&seed, &config)).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        match SaveFormat::from_path(&path) {
+            SaveFormat::Ron => {
+                let content = String::from_utf8(bytes).unwrap().replacen(
+                    &format!("version: {}", SAVE_VERSION),
+                    &format!("version: {}", version),
+                    1,
+                );
+                bytes = content.into_bytes();
+            }
+            SaveFormat::Binary => {
+                let offset = BINARY_MAGIC.len();
+                bytes[offset..offset + 4].copy_from_slice(&version.to_le_bytes());
+            }
+        }
+        fs::write(&path, bytes).unwrap();
+        let saved = load_map(path.clone());
+        fs::remove_file(path).unwrap();
+        return saved;
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for name in ["version.ron", "version.bin"] {
+            for version in [0, SAVE_VERSION + 1] {
+                assert!(matches!(
+                    load_with_version(name, version),
+                    Err(SaveError::UnsupportedVersion(_, rejected)) if rejected == version
+                ));
+            }
+            assert!(load_with_version(name, SAVE_VERSION).is_ok());
+        }
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let path = get_save_path("invalid-config.ron");
+        let seed = MapSeed(7);
+        let config = small_config();
+        let map = build_map(&seed, &config);
+        let mut broken_config = config.clone();
+        broken_config.noise.gain = 0.6..0.5;
+        save_map(path.clone(), &seed, &broken_config, &map).unwrap();
+        let saved = load_map(path.clone());
+        fs::remove_file(path).unwrap();
+        assert!(matches!(saved, Err(SaveError::InvalidConfig(_, _))));
+    }
+
+    #[test]
+    fn ron_fields_are_removed() {
+        assert_eq!(
+            remove_ron_field(
+                "(a:1,desert_band:(thickness:(start:0.05,end:0.1),jitter:0.1),b:[2])".to_string(),
+                "desert_band"
+            ),
+            "(a:1,b:[2])"
+        );
+        assert_eq!(
+            remove_ron_field(
+                "(a: 1, desert_band: (jitter: 0.1))".to_string(),
+                "desert_band"
+            ),
+            "(a: 1,)"
+        );
+        assert_eq!(
+            remove_ron_field("(a: 1)".to_string(), "desert_band"),
+            "(a: 1)"
+        );
+    }
+
+    #[test]
+    fn version_2_saves_lose_their_desert_band() {
+        let path = get_save_path("version-2.ron");
+        let seed = MapSeed(7);
+        let config = small_config();
+        save_map(path.clone(), &seed, &config, &build_map(&seed, &config)).unwrap();
+        let content = fs::read_to_string(&path).unwrap().replacen(
+            "config: (",
+            "config: (desert_band: (thickness: (start: 0.05, end: 0.1), jitter: 0.1), ",
+            1,
+        );
+        fs::write(&path, &content).unwrap();
+        // Today's configurations have no desert band
+        assert!(matches!(
+            load_map(path.clone()),
+            Err(SaveError::Parse(_, _))
+        ));
+
+        let content = content.replacen(&format!("version: {}", SAVE_VERSION), "version: 2", 1);
+        fs::write(&path, content).unwrap();
+        let saved = load_map(path.clone());
+        fs::remove_file(path).unwrap();
+        assert_eq!(saved.unwrap().config, config);
+    }
+
+    #[test]
+    fn version_3_saves_find_their_lakes() {
+        // Version 3 maps only had oceans, and the fish living in them
+        let mut map = draw_map(&[
+            "~~~~~~~", //
+            "~.....~", //
+            "~.~~..~", //
+            "~.~~..~", //
+            "~.....~", //
+            "~~~~~~~", //
+        ]);
+        for coordinates in [(0, 0), (2, 2)] {
+            map.get_mut(&coordinates)
+                .unwrap()
+                .layers
+                .insert(Layer::Special, Kind::SKind(SpecialKind::Fish));
+        }
+        let config = MapGenConfig {
+            width: 6,
+            height: 5,
+            ..MapGenConfig::default()
+        };
+        let path = get_save_path("version-3.ron");
+        save_map(path.clone(), &MapSeed(7), &config, &map).unwrap();
+        let content = fs::read_to_string(&path).unwrap().replacen(
+            &format!("version: {}", SAVE_VERSION),
+            "version: 3",
+            1,
+        );
+        fs::write(&path, content).unwrap();
+        let saved = load_map(path.clone());
+        fs::remove_file(path).unwrap();
+
+        let saved = saved.unwrap();
+        let get_kind = |coordinates: (i32, i32), layer: Layer| {
+            return saved.map[&coordinates].layers.get(&layer).copied();
+        };
+        assert_eq!(
+            get_kind((2, 2), Layer::Feature),
+            Some(Kind::FKind(FeatureKind::Lake))
+        );
+        assert_eq!(
+            get_kind((2, 2), Layer::Special),
+            Some(Kind::SKind(SpecialKind::FreshwaterFish))
+        );
+        assert_eq!(
+            get_kind((0, 0), Layer::Feature),
+            Some(Kind::FKind(FeatureKind::Ocean))
+        );
+        assert_eq!(
+            get_kind((0, 0), Layer::Special),
+            Some(Kind::SKind(SpecialKind::Fish))
+        );
+        assert_eq!(get_kind((1, 1), Layer::Feature), None);
+        assert_ne!(saved.map[&(2, 2)].water_body, saved.map[&(0, 0)].water_body);
+    }
+
+    #[test]
+    fn version_4_saves_find_their_deep_ocean() {
+        // Version 4 maps only had coastal waters, with fish anywhere on them
+        let mut map = draw_map(&[
+            ".......", //
+            "~~~~~~~", //
+            "~~~~~~~", //
+            "~~~~~~~", //
+            "~~~~~~~", //
+        ]);
+        for tile in map.values_mut() {
+            tile.climate.elevation = -1.;
+        }
+        for coordinates in [(3, 0), (3, 3)] {
+            map.get_mut(&coordinates)
+                .unwrap()
+                .layers
+                .insert(Layer::Special, Kind::SKind(SpecialKind::Fish));
+        }
+        let config = MapGenConfig {
+            width: 6,
+            height: 4,
+            ..MapGenConfig::default()
+        };
+        let path = get_save_path("version-4.ron");
+        save_map(path.clone(), &MapSeed(7), &config, &map).unwrap();
+        let content = fs::read_to_string(&path).unwrap().replacen(
+            &format!("version: {}", SAVE_VERSION),
+            "version: 4",
+            1,
+        );
+        fs::write(&path, content).unwrap();
+        let saved = load_map(path.clone());
+        fs::remove_file(path).unwrap();
+
+        let saved = saved.unwrap();
+        let get_kind = |coordinates: (i32, i32), layer: Layer| {
+            return saved.map[&coordinates].layers.get(&layer).copied();
+        };
+        // Beyond the coast width (of 2 by default)
+        assert_eq!(
+            get_kind((3, 1), Layer::Feature),
+            Some(Kind::FKind(FeatureKind::DeepOcean))
+        );
+        assert_eq!(
+            get_kind((3, 0), Layer::Feature),
+            Some(Kind::FKind(FeatureKind::DeepOcean))
+        );
+        assert_eq!(get_kind((3, 0), Layer::Special), None);
+        assert_eq!(
+            get_kind((3, 2), Layer::Feature),
+            Some(Kind::FKind(FeatureKind::Ocean))
+        );
+        assert_eq!(
+            get_kind((3, 3), Layer::Special),
+            Some(Kind::SKind(SpecialKind::Fish))
+        );
+        assert_eq!(saved.map[&(3, 0)].water_body, saved.map[&(3, 3)].water_body);
+    }
+}
@@ -0,0 +1,236 @@
+use std::ops::Range;
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    config::MapGenConfig, water::is_water, FeatureKind, Kind, Layer, Map, MapSeed, SettlementKind,
+    SpecialKind, Tile,
+};
+
+/// First half of settlement names
+const NAME_PREFIXES: [&str; 20] = [
+    "Ash", "Bel", "Cor", "Dun", "Elm", "Fen", "Gal", "Hol", "Ing", "Kes", "Lor", "Mar", "Nor",
+    "Oak", "Pen", "Ros", "Sal", "Tor", "Vel", "Wen",
+];
+/// Second half of settlement names
+const NAME_SUFFIXES: [&str; 12] = [
+    "by", "ford", "ham", "ton", "wick", "stead", "mouth", "field", "bury", "dale", "mere", "gate",
+];
+
+/// Everything about a settlement that is not stored on its tile
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlementInfo {
+    pub name: String,
+    pub population: u32,
+}
+
+/// Population range of each settlement Kind
+fn get_population_range(kind: &SettlementKind) -> Range<u32> {
+    return match kind {
+        SettlementKind::Village => 100..1_000,
+        SettlementKind::Town => 1_000..5_000,
+        SettlementKind::City => 5_000..20_000,
+        SettlementKind::Fort => 50..300,
+    };
+}
+
+/// Describe the settlement standing at the given coordinates.
+///
+/// Names and populations are derived from the map seed and the coordinates, so
+/// they do not need to be stored along the Map to stay the same across runs.
+pub fn describe_settlement(
+    map_seed: &MapSeed,
+    coordinates: &(i32, i32),
+    kind: &SettlementKind,
+) -> SettlementInfo {
+    let mut pseudo_rng_instance = StdRng::seed_from_u64(
+        map_seed.0 ^ ((coordinates.0 as u64) << 32 | coordinates.1 as u32 as u64),
+    );
+    return SettlementInfo {
+        name: format!(
+            "{}{}",
+            NAME_PREFIXES[pseudo_rng_instance.gen_range(0..NAME_PREFIXES.len())],
+            NAME_SUFFIXES[pseudo_rng_instance.gen_range(0..NAME_SUFFIXES.len())]
+        ),
+        population: pseudo_rng_instance.gen_range(get_population_range(kind)),
+    };
+}
+
+/// Whether people can settle on a tile (anywhere but on water and mountains)
+pub fn can_settle(tile: &Tile) -> bool {
+    return !is_water(tile)
+        && tile.layers.get(&Layer::Special) != Some(&Kind::SKind(SpecialKind::Mountain));
+}
+
+/// How many food specials (Corn, Fish, Freshwater Fish) lie around a tile
+fn count_food_around(map: &Map, coordinates: &(i32, i32), radius: i32) -> usize {
+    let mut count = 0;
+    for w in -radius..=radius {
+        for h in -radius..=radius {
+            let special = map
+                .get(&(coordinates.0 + w, coordinates.1 + h))
+                .and_then(|tile| tile.layers.get(&Layer::Special));
+            if matches!(
+                special,
+                Some(Kind::SKind(
+                    SpecialKind::Corn | SpecialKind::Fish | SpecialKind::FreshwaterFish
+                ))
+            ) {
+                count += 1;
+            }
+        }
+    }
+    return count;
+}
+
+/// Scatter settlements on habitable land.
+///
+/// Every habitable tile is a candidate, weighted by the food specials around
+/// it, and candidates too close to an existing settlement are rejected. The
+/// settlement Kind then depends on the spot: forts guard the hills, and the
+/// more food around, the bigger the settlement.
+pub fn place_settlements(pseudo_rng_instance: &mut StdRng, map: &mut Map, config: &MapGenConfig) {
+    let settings = &config.settlements;
+
+    // Candidates are listed in a stable order so that the same seed always
+    // gives the same settlements
+    let mut candidates: Vec<((i32, i32), usize)> = Vec::new();
+    for w in 0..=config.width {
+        for h in 0..=config.height {
+            if map.get(&(w, h)).is_some_and(can_settle) {
+                candidates.push((
+                    (w, h),
+                    count_food_around(map, &(w, h), settings.food_radius),
+                ));
+            }
+        }
+    }
+    let Ok(distribution) = WeightedIndex::new(
+        candidates
+            .iter()
+            .map(|(_, food)| 1. + *food as f32 * settings.food_weight),
+    ) else {
+        // No habitable land at all
+        return;
+    };
+
+    let mut settlements: Vec<(i32, i32)> = Vec::new();
+    // Give up after a while, in case the map is too crowded
+    for _ in 0..settings.count * 20 {
+        if settlements.len() >= settings.count {
+            break;
+        }
+
+        let (coordinates, food) = candidates[distribution.sample(pseudo_rng_instance)];
+        if settlements.iter().any(|other| {
+            (other.0 - coordinates.0)
+                .abs()
+                .max((other.1 - coordinates.1).abs())
+                < settings.min_spacing
+        }) {
+            continue;
+        }
+
+        let is_on_hill = map.get(&coordinates).unwrap().layers.get(&Layer::Feature)
+            == Some(&Kind::FKind(FeatureKind::Hill));
+        let kind = match food {
+            _ if is_on_hill => SettlementKind::Fort,
+            food if food >= 3 => SettlementKind::City,
+            food if food >= 1 => SettlementKind::Town,
+            _ => SettlementKind::Village,
+        };
+        map.get_mut(&coordinates)
+            .unwrap()
+            .layers
+            .insert(Layer::Settlement, Kind::StKind(kind));
+        settlements.push(coordinates);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::{config::SettlementsConfig, tests::draw_map, Tile};
+
+    /// Place settlements on a hand-drawn map, which must be `width + 1` tiles
+    /// wide and `height + 1` tiles high
+    fn settle(map: &mut Map, seed: u64, settlements: SettlementsConfig) -> Vec<(i32, i32)> {
+        let config = MapGenConfig {
+            width: map.keys().map(|coordinates| coordinates.0).max().unwrap(),
+            height: map.keys().map(|coordinates| coordinates.1).max().unwrap(),
+            settlements,
+            ..MapGenConfig::default()
+        };
+        place_settlements(&mut StdRng::seed_from_u64(seed), map, &config);
+        let mut settled: Vec<(i32, i32)> = map
+            .iter()
+            .filter(|(_, tile)| tile.layers.contains_key(&Layer::Settlement))
+            .map(|(coordinates, _)| *coordinates)
+            .collect();
+        settled.sort();
+        return settled;
+    }
+
+    #[test]
+    fn settlements_keep_their_distance() {
+        for seed in 0..10 {
+            let mut map = draw_map(&["..........."; 11]);
+            let settled = settle(
+                &mut map,
+                seed,
+                SettlementsConfig {
+                    count: 50,
+                    min_spacing: 3,
+                    ..SettlementsConfig::default()
+                },
+            );
+            assert!(settled.len() > 1);
+            for (i, a) in settled.iter().enumerate() {
+                for b in &settled[i + 1..] {
+                    assert!((a.0 - b.0).abs().max((a.1 - b.1).abs()) >= 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn settlements_avoid_water_and_mountains() {
+        for seed in 0..10 {
+            let mut map = draw_map(&["~~~~~~", "~.m.m~", "~m.m.~", "~~~~~~"]);
+            let settled = settle(
+                &mut map,
+                seed,
+                SettlementsConfig {
+                    count: 10,
+                    min_spacing: 1,
+                    ..SettlementsConfig::default()
+                },
+            );
+            assert_eq!(settled, vec![(1, 2), (2, 1), (3, 2), (4, 1)]);
+        }
+    }
+
+    #[test]
+    fn settlements_prefer_food() {
+        let corn = |tile: &mut Tile| {
+            tile.layers
+                .insert(Layer::Special, Kind::SKind(SpecialKind::Corn));
+        };
+        for seed in 0..20 {
+            let mut map = draw_map(&["...................."]);
+            corn(map.get_mut(&(17, 0)).unwrap());
+            let settled = settle(
+                &mut map,
+                seed,
+                SettlementsConfig {
+                    count: 1,
+                    food_radius: 1,
+                    food_weight: 1000.,
+                    ..SettlementsConfig::default()
+                },
+            );
+            assert_eq!(settled.len(), 1);
+            assert!((settled[0].0 - 17).abs() <= 1, "{:?}", settled);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use super::{
+    water::{is_deep_water, is_water},
+    Map, Tile, UnitKind,
+};
+
+/// Why a Unit cannot move to a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// There is no tile at these coordinates
+    OutOfMap((i32, i32)),
+    /// The tile is not reachable by this Unit Kind
+    Forbidden((i32, i32), UnitKind),
+    /// Units only move from a tile to one of its 8 neighbours
+    NotAdjacent((i32, i32), (i32, i32)),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MoveError::OutOfMap(coordinates) => {
+                write!(f, "There is no tile at {:?}", coordinates)
+            }
+            MoveError::Forbidden(coordinates, kind) => {
+                write!(f, "{:?} cannot go on the tile at {:?}", kind, coordinates)
+            }
+            MoveError::NotAdjacent(from, to) => {
+                write!(f, "Cannot move from {:?} to {:?} in one step", from, to)
+            }
+        };
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Whether a Unit Kind sails on water rather than walking on land
+pub fn sails(kind: &UnitKind) -> bool {
+    return match kind {
+        UnitKind::Boat | UnitKind::Ship => true,
+        UnitKind::Settler | UnitKind::Worker | UnitKind::Soldier | UnitKind::Cannon => false,
+    };
+}
+
+/// Whether a Unit Kind can venture on the deep ocean: boats hug the coast,
+/// ships can cross the open sea.
+pub fn is_ocean_going(kind: &UnitKind) -> bool {
+    return *kind == UnitKind::Ship;
+}
+
+/// Whether a Unit can stand on a tile: sailing units on coastal waters and
+/// lakes (and on the deep ocean as well if they are ocean-going), while every
+/// other unit walks on land.
+pub fn can_enter(tile: &Tile, kind: &UnitKind) -> bool {
+    if sails(kind) {
+        return is_water(tile) && (is_ocean_going(kind) || !is_deep_water(tile));
+    }
+    return !is_water(tile);
+}
+
+/// Ensure a Unit can stand on the tile at the given coordinates
+pub fn check_position(
+    map: &Map,
+    coordinates: &(i32, i32),
+    kind: &UnitKind,
+) -> Result<(), MoveError> {
+    let tile = map
+        .get(coordinates)
+        .ok_or(MoveError::OutOfMap(*coordinates))?;
+    if !can_enter(tile, kind) {
+        return Err(MoveError::Forbidden(*coordinates, *kind));
+    }
+    return Ok(());
+}
+
+/// Ensure a Unit can move in one step from a tile to another
+pub fn check_step(
+    map: &Map,
+    from: &(i32, i32),
+    to: &(i32, i32),
+    kind: &UnitKind,
+) -> Result<(), MoveError> {
+    if from == to || (from.0 - to.0).abs() > 1 || (from.1 - to.1).abs() > 1 {
+        return Err(MoveError::NotAdjacent(*from, *to));
+    }
+    return check_position(map, to, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::tests::draw_map;
+
+    const LAND_UNITS: [UnitKind; 4] = [
+        UnitKind::Settler,
+        UnitKind::Worker,
+        UnitKind::Soldier,
+        UnitKind::Cannon,
+    ];
+
+    #[test]
+    fn boats_sail_and_other_units_walk() {
+        let map = draw_map(&[".~"]);
+        for kind in LAND_UNITS {
+            assert!(can_enter(&map[&(0, 0)], &kind));
+            assert!(!can_enter(&map[&(1, 0)], &kind));
+        }
+        assert!(!can_enter(&map[&(0, 0)], &UnitKind::Boat));
+        assert!(can_enter(&map[&(1, 0)], &UnitKind::Boat));
+    }
+
+    #[test]
+    fn only_ships_venture_on_the_deep_ocean() {
+        let map = draw_map(&[".~dl"]);
+        // Land units stay off every water, deep or not
+        for kind in LAND_UNITS {
+            for coordinates in [(1, 0), (2, 0), (3, 0)] {
+                assert!(!can_enter(&map[&coordinates], &kind));
+            }
+            assert_eq!(
+                check_step(&map, &(0, 0), &(1, 0), &kind),
+                Err(MoveError::Forbidden((1, 0), kind))
+            );
+        }
+        for kind in [UnitKind::Boat, UnitKind::Ship] {
+            assert!(!can_enter(&map[&(0, 0)], &kind));
+            assert!(can_enter(&map[&(1, 0)], &kind));
+            assert!(can_enter(&map[&(3, 0)], &kind));
+        }
+        assert!(!can_enter(&map[&(2, 0)], &UnitKind::Boat));
+        assert_eq!(
+            check_step(&map, &(1, 0), &(2, 0), &UnitKind::Boat),
+            Err(MoveError::Forbidden((2, 0), UnitKind::Boat))
+        );
+        assert!(can_enter(&map[&(2, 0)], &UnitKind::Ship));
+        assert_eq!(check_step(&map, &(1, 0), &(2, 0), &UnitKind::Ship), Ok(()));
+    }
+
+    #[test]
+    fn positions_must_be_on_the_map_and_allowed() {
+        let map = draw_map(&[".~"]);
+        assert_eq!(check_position(&map, &(0, 0), &UnitKind::Worker), Ok(()));
+        assert_eq!(
+            check_position(&map, &(1, 0), &UnitKind::Worker),
+            Err(MoveError::Forbidden((1, 0), UnitKind::Worker))
+        );
+        assert_eq!(
+            check_position(&map, &(2, 0), &UnitKind::Boat),
+            Err(MoveError::OutOfMap((2, 0)))
+        );
+    }
+
+    #[test]
+    fn steps_go_to_a_neighbour() {
+        let map = draw_map(&["...", "...", "..~"]);
+        assert_eq!(
+            check_step(&map, &(1, 1), &(0, 2), &UnitKind::Soldier),
+            Ok(())
+        );
+        assert_eq!(
+            check_step(&map, &(1, 1), &(1, 1), &UnitKind::Soldier),
+            Err(MoveError::NotAdjacent((1, 1), (1, 1)))
+        );
+        assert_eq!(
+            check_step(&map, &(0, 0), &(2, 1), &UnitKind::Soldier),
+            Err(MoveError::NotAdjacent((0, 0), (2, 1)))
+        );
+        assert_eq!(
+            check_step(&map, &(1, 1), &(2, 0), &UnitKind::Soldier),
+            Err(MoveError::Forbidden((2, 0), UnitKind::Soldier))
+        );
+        assert_eq!(
+            check_step(&map, &(2, 0), &(3, 0), &UnitKind::Boat),
+            Err(MoveError::OutOfMap((3, 0)))
+        );
+    }
+}
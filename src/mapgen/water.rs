@@ -0,0 +1,222 @@
+//! Bodies of water: the ocean, the inland seas and the lakes.
+//!
+//! A water body is a group of sea tiles (coastal and deep waters alike) or of
+//! lake tiles touching each other (diagonals included, like units move). Every
+//! water tile knows the id of its body, so that gameplay can tell whether two
+//! tiles share the same water (can this boat get there, does this lake feed
+//! this village, etc.).
+//!
+//! The sea is shallow along the coasts, and deep once both far enough from
+//! land and low enough in the height noise map.
+
+use std::collections::VecDeque;
+
+use bevy::utils::{HashMap, HashSet};
+
+use super::{config::MapGenConfig, query::MapQuery, FeatureKind, Kind, Layer, Map, Tile};
+
+/// Identifies a water body, among the ones of a given map
+pub type WaterBodyId = u32;
+
+/// Whether a tile is under water (sea or lake)
+pub fn is_water(tile: &Tile) -> bool {
+    return matches!(
+        tile.layers.get(&Layer::Feature),
+        Some(Kind::FKind(
+            FeatureKind::Ocean | FeatureKind::DeepOcean | FeatureKind::Lake
+        ))
+    );
+}
+
+/// Whether a tile is out on the open sea, away from the coasts
+pub fn is_deep_water(tile: &Tile) -> bool {
+    return tile.layers.get(&Layer::Feature) == Some(&Kind::FKind(FeatureKind::DeepOcean));
+}
+
+/// The Kind of body of water a tile belongs to, coastal and deep waters being
+/// part of the same sea
+fn get_body_kind(tile: &Tile) -> Option<FeatureKind> {
+    return match tile.layers.get(&Layer::Feature) {
+        Some(Kind::FKind(FeatureKind::Ocean | FeatureKind::DeepOcean)) => Some(FeatureKind::Ocean),
+        Some(Kind::FKind(FeatureKind::Lake)) => Some(FeatureKind::Lake),
+        _ => None,
+    };
+}
+
+/// Group the water tiles of a map in bodies, listed in a stable order (the
+/// one of their first tile, column by column)
+fn find_water_bodies(map: &Map) -> Vec<Vec<(i32, i32)>> {
+    let mut coordinates: Vec<(i32, i32)> = map.keys().copied().collect();
+    coordinates.sort();
+
+    let mut visited: HashSet<(i32, i32)> = HashSet::new();
+    let mut bodies: Vec<Vec<(i32, i32)>> = Vec::new();
+    for start in coordinates {
+        let body_kind = get_body_kind(map.get(&start).unwrap());
+        if body_kind.is_none() || visited.contains(&start) {
+            continue;
+        }
+
+        // Flood fill through the tiles of the same body Kind
+        let mut body = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        visited.insert(start);
+        while let Some(current) = queue.pop_front() {
+            body.push(current);
+            for (neighbour, neighbour_tile) in map.neighbours(&current) {
+                if get_body_kind(neighbour_tile) == body_kind && visited.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        bodies.push(body);
+    }
+    return bodies;
+}
+
+/// Give every water tile the id of its water body (and remove it from the
+/// others), to be done again whenever water is added or removed
+pub fn label_water_bodies(map: &mut Map) {
+    for tile in map.values_mut() {
+        tile.water_body = None;
+    }
+    for (id, body) in find_water_bodies(map).into_iter().enumerate() {
+        for coordinates in body {
+            map.get_mut(&coordinates).unwrap().water_body = Some(id as WaterBodyId);
+        }
+    }
+}
+
+/// Turn into lakes the bodies of sea that do not reach the edges of the map,
+/// unless they are large enough to be inland seas
+pub fn place_lakes(map: &mut Map, config: &MapGenConfig) {
+    let is_on_edge = |coordinates: &(i32, i32)| {
+        return coordinates.0 == 0
+            || coordinates.1 == 0
+            || coordinates.0 == config.width
+            || coordinates.1 == config.height;
+    };
+
+    for body in find_water_bodies(map) {
+        if body.len() > config.lakes.max_size || body.iter().any(is_on_edge) {
+            continue;
+        }
+        for coordinates in body {
+            map.get_mut(&coordinates)
+                .unwrap()
+                .layers
+                .insert(Layer::Feature, Kind::FKind(FeatureKind::Lake));
+        }
+    }
+}
+
+/// Turn into deep ocean the sea tiles that are both further from land than
+/// the coast width and deeper than the shallows, so that coastal waters
+/// follow every coast (and spread wider over shallow seas).
+pub fn place_deep_ocean(map: &mut Map, config: &MapGenConfig) {
+    // Distance (in steps, diagonals included) to the nearest land, spreading
+    // from the land itself up to the coast width
+    let mut distances: HashMap<(i32, i32), usize> = HashMap::new();
+    let mut queue: VecDeque<(i32, i32)> = VecDeque::new();
+    for (coordinates, tile) in map.iter() {
+        if !is_water(tile) {
+            distances.insert(*coordinates, 0);
+            queue.push_back(*coordinates);
+        }
+    }
+    while let Some(current) = queue.pop_front() {
+        let distance = distances[&current] + 1;
+        if distance > config.ocean.coast_width {
+            continue;
+        }
+        for (neighbour, _) in map.neighbours(&current) {
+            if !distances.contains_key(&neighbour) {
+                distances.insert(neighbour, distance);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    let shallows_floor = config.thresholds.plain - config.ocean.shallow_depth;
+    for (coordinates, tile) in map.iter_mut() {
+        if tile.layers.get(&Layer::Feature) == Some(&Kind::FKind(FeatureKind::Ocean))
+            && !distances.contains_key(coordinates)
+            && tile.climate.elevation < shallows_floor
+        {
+            tile.layers
+                .insert(Layer::Feature, Kind::FKind(FeatureKind::DeepOcean));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::{build_map, config::LakesConfig, tests::draw_map, MapSeed};
+
+    /// Configuration of a hand-drawn map, whose rows are all as long
+    fn drawn_config(rows: &[&str], max_lake_size: usize) -> MapGenConfig {
+        return MapGenConfig {
+            width: rows[0].len() as i32 - 1,
+            height: rows.len() as i32 - 1,
+            lakes: LakesConfig {
+                max_size: max_lake_size,
+            },
+            ..MapGenConfig::default()
+        };
+    }
+
+    #[test]
+    fn water_bodies_are_told_apart() {
+        let map = draw_map(&["~~...", "~..l.", "...l.", "...~.", "....~"]);
+        let body = |coordinates: (i32, i32)| map[&coordinates].water_body;
+
+        // Tiles touching each other (diagonals included) share their body
+        assert!(body((0, 4)).is_some());
+        assert_eq!(body((0, 4)), body((1, 4)));
+        assert_eq!(body((0, 4)), body((0, 3)));
+        assert_eq!(body((3, 3)), body((3, 2)));
+        assert_eq!(body((3, 1)), body((4, 0)));
+
+        // Others do not, even when a lake touches the ocean
+        let bodies = [body((0, 4)), body((3, 3)), body((3, 1))];
+        for (index, id) in bodies.iter().enumerate() {
+            assert!(id.is_some());
+            assert!(!bodies[index + 1..].contains(id));
+        }
+        assert!(body((2, 2)).is_none());
+    }
+
+    #[test]
+    fn lakes_are_small_and_inland() {
+        let rows = ["~......", ".......", "..~.~~.", "..~..~.", "......."];
+        let mut map = draw_map(&rows);
+        place_lakes(&mut map, &drawn_config(&rows, 2));
+        let feature = |coordinates: (i32, i32)| map[&coordinates].layers[&Layer::Feature];
+
+        // Reaching the edges
+        assert_eq!(feature((0, 4)), Kind::FKind(FeatureKind::Ocean));
+        // Inland and small enough
+        assert_eq!(feature((2, 2)), Kind::FKind(FeatureKind::Lake));
+        assert_eq!(feature((2, 1)), Kind::FKind(FeatureKind::Lake));
+        // Inland but too large
+        assert_eq!(feature((4, 2)), Kind::FKind(FeatureKind::Ocean));
+        assert_eq!(feature((5, 1)), Kind::FKind(FeatureKind::Ocean));
+    }
+
+    #[test]
+    fn lakes_never_touch_the_ocean() {
+        for seed in 0..4 {
+            let map = build_map(&MapSeed(seed), &MapGenConfig::default());
+            for (coordinates, tile) in map.iter() {
+                if tile.layers.get(&Layer::Feature) != Some(&Kind::FKind(FeatureKind::Lake)) {
+                    continue;
+                }
+                assert!(map
+                    .neighbours(coordinates)
+                    .iter()
+                    .all(|(_, neighbour)| get_body_kind(neighbour) != Some(FeatureKind::Ocean)));
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Tiny overviews of maps, where every tile is a single pixel, to eyeball (or
+//! compute statistics on) lots of generated maps at once.
+
+use image::{Rgba, RgbaImage};
+
+use crate::mapgen::{FeatureKind, Kind, Layer, Map, SettlementKind, SpecialKind, TerrainKind};
+
+/// Colors of the «31» palette (https://lospec.com/palette-list/31) the
+/// sprites are drawn with
+const OCEAN_BLUE: [u8; 3] = [0x5c, 0x8b, 0xa8];
+const DEEP_OCEAN_BLUE: [u8; 3] = [0x4e, 0x66, 0x79];
+const RIVER_BLUE: [u8; 3] = [0x94, 0xd5, 0xd5];
+const LAKE_BLUE: [u8; 3] = [0x72, 0xb6, 0xcf];
+const PLAIN_GREEN: [u8; 3] = [0x81, 0x94, 0x47];
+const DESERT_YELLOW: [u8; 3] = [0xfc, 0xef, 0x8d];
+const TUNDRA_GREY: [u8; 3] = [0x82, 0xad, 0xa9];
+const SNOW_WHITE: [u8; 3] = [0xc7, 0xdc, 0xd0];
+const GRASSLAND_GREEN: [u8; 3] = [0xa6, 0xb0, 0x4f];
+const SAVANNA_OLIVE: [u8; 3] = [0xb2, 0xba, 0x90];
+const JUNGLE_GREEN: [u8; 3] = [0x44, 0x70, 0x2d];
+const SWAMP_TEAL: [u8; 3] = [0x65, 0x8d, 0x78];
+const FOREST_GREEN: [u8; 3] = [0x2f, 0x4d, 0x2f];
+const HILL_BROWN: [u8; 3] = [0x73, 0x4c, 0x44];
+const MOUNTAIN_GREY: [u8; 3] = [0x9b, 0xab, 0xb2];
+const CORN_ORANGE: [u8; 3] = [0xf2, 0xa5, 0x61];
+const LUMBER_GREEN: [u8; 3] = [0x80, 0xb8, 0x78];
+const FISH_WHITE: [u8; 3] = [0xda, 0xfa, 0xde];
+const SETTLEMENT_RED: [u8; 3] = [0xe0, 0x6b, 0x51];
+const FORT_BLACK: [u8; 3] = [0x2e, 0x22, 0x2f];
+
+/// The overview color of a Kind, if it shows on overviews at all
+/// (developments do not, since roads would hide everything else)
+pub fn get_kind_color(kind: &Kind) -> Option<[u8; 3]> {
+    return match kind {
+        Kind::TKind(TerrainKind::Plain) => Some(PLAIN_GREEN),
+        Kind::TKind(TerrainKind::Desert) => Some(DESERT_YELLOW),
+        Kind::TKind(TerrainKind::Tundra) => Some(TUNDRA_GREY),
+        Kind::TKind(TerrainKind::Snow) => Some(SNOW_WHITE),
+        Kind::TKind(TerrainKind::Grassland) => Some(GRASSLAND_GREEN),
+        Kind::TKind(TerrainKind::Savanna) => Some(SAVANNA_OLIVE),
+        Kind::TKind(TerrainKind::Jungle) => Some(JUNGLE_GREEN),
+        Kind::TKind(TerrainKind::Swamp) => Some(SWAMP_TEAL),
+        Kind::FKind(FeatureKind::Ocean) => Some(OCEAN_BLUE),
+        Kind::FKind(FeatureKind::DeepOcean) => Some(DEEP_OCEAN_BLUE),
+        Kind::FKind(FeatureKind::Forest) => Some(FOREST_GREEN),
+        Kind::FKind(FeatureKind::Hill) => Some(HILL_BROWN),
+        Kind::FKind(FeatureKind::River) => Some(RIVER_BLUE),
+        Kind::FKind(FeatureKind::Lake) => Some(LAKE_BLUE),
+        Kind::SKind(SpecialKind::Mountain) => Some(MOUNTAIN_GREY),
+        Kind::SKind(SpecialKind::Corn) => Some(CORN_ORANGE),
+        Kind::SKind(SpecialKind::Lumber) => Some(LUMBER_GREEN),
+        Kind::SKind(SpecialKind::Fish | SpecialKind::FreshwaterFish) => Some(FISH_WHITE),
+        Kind::DKind(_) => None,
+        Kind::StKind(SettlementKind::Fort) => Some(FORT_BLACK),
+        Kind::StKind(_) => Some(SETTLEMENT_RED),
+    };
+}
+
+/// Draw a map with one pixel per tile, colored by its topmost layer that shows
+/// on overviews. Tiles missing from the map are left transparent.
+pub fn render_overview(map: &Map, width: i32, height: i32) -> RgbaImage {
+    let mut overview = RgbaImage::new((width + 1) as u32, (height + 1) as u32);
+    for w in 0..=width {
+        for h in 0..=height {
+            let color = map.get(&(w, h)).and_then(|tile| {
+                Layer::ALL
+                    .iter()
+                    .rev()
+                    .find_map(|layer| tile.layers.get(layer).and_then(get_kind_color))
+            });
+            if let Some([r, g, b]) = color {
+                // Image rows go downward while map rows go upward
+                overview.put_pixel(w as u32, (height - h) as u32, Rgba([r, g, b, 255]));
+            }
+        }
+    }
+    return overview;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mapgen::{build_map, config::MapGenConfig, MapSeed},
+        tilesets::TILESETS,
+    };
+
+    #[test]
+    fn every_kind_on_the_map_has_a_color() {
+        let config = MapGenConfig {
+            width: 48,
+            height: 32,
+            ..MapGenConfig::default()
+        };
+        let map = build_map(&MapSeed(3), &config);
+        for tile in map.values() {
+            for kind in tile.layers.values() {
+                assert!(
+                    matches!(kind, Kind::DKind(_)) || get_kind_color(kind).is_some(),
+                    "{:?} has no overview color",
+                    kind
+                );
+            }
+        }
+
+        let overview = render_overview(&map, config.width, config.height);
+        assert_eq!(overview.width(), (config.width + 1) as u32);
+        assert_eq!(overview.height(), (config.height + 1) as u32);
+        assert!(overview.pixels().all(|pixel| pixel.0[3] == 255));
+    }
+
+    #[test]
+    fn kinds_have_their_own_color() {
+        // Settlements are told apart by their sprites, not on overviews, and
+        // freshwater fish are drawn like sea fish
+        let colors: Vec<[u8; 3]> = TILESETS
+            .iter()
+            .filter(|(kind, ..)| {
+                !matches!(
+                    kind,
+                    Kind::DKind(_) | Kind::StKind(_) | Kind::SKind(SpecialKind::FreshwaterFish)
+                )
+            })
+            .map(|(kind, ..)| get_kind_color(kind).unwrap())
+            .collect();
+        for (index, color) in colors.iter().enumerate() {
+            assert!(
+                !colors[index + 1..].contains(color),
+                "{:?} is used twice",
+                color
+            );
+        }
+    }
+}
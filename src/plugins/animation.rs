@@ -0,0 +1,138 @@
+use std::fs;
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*, utils::HashMap};
+use bevy_spritesim::mapgen::Kind;
+use serde::{Deserialize, Serialize};
+
+use super::tilemap::{TilemapMaterial, Tilesets};
+
+/// Where animation settings live, relative to the assets folder
+const ANIMATIONS_PATH: &str = "animations.ron";
+
+/// How a tileset is animated
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationSettings {
+    /// How many frames (starting from the first one) are cycled through
+    pub frame_count: usize,
+    /// In seconds
+    pub frame_duration: f32,
+}
+
+/// Animation settings of every tileset, as stored in `assets/animations.ron`.
+///
+/// Kinds that are not listed use the default settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationsData {
+    pub default: AnimationSettings,
+    #[serde(default)]
+    pub kinds: HashMap<Kind, AnimationSettings>,
+}
+
+impl AnimationsData {
+    /// Read the animation settings from the assets folder and ensure every
+    /// tileset holds enough frames for them.
+    pub fn load(available_frames: usize) -> Self {
+        let path = FileAssetReader::get_base_path()
+            .join("assets")
+            .join(ANIMATIONS_PATH);
+        let data: AnimationsData = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|content| ron::from_str(&content).map_err(|error| error.to_string()))
+            .unwrap_or_else(|error| panic!("Cannot load «{}»: {}", path.display(), error));
+
+        for settings in std::iter::once(&data.default).chain(data.kinds.values()) {
+            if settings.frame_count < 1 || settings.frame_count > available_frames {
+                panic!(
+                    "Invalid animation in «{}»: frame_count must be between 1 and {}, got {}",
+                    path.display(),
+                    available_frames,
+                    settings.frame_count
+                );
+            }
+            if settings.frame_duration <= 0. {
+                panic!(
+                    "Invalid animation in «{}»: frame_duration must be positive, got {}",
+                    path.display(),
+                    settings.frame_duration
+                );
+            }
+        }
+
+        return data;
+    }
+
+    /// Retrieve the settings of a given Kind
+    pub fn get(&self, kind: &Kind) -> &AnimationSettings {
+        return self.kinds.get(kind).unwrap_or(&self.default);
+    }
+}
+
+/// Where a tileset currently is in its animation
+struct AnimationTrack {
+    timer: Timer,
+    frame_count: usize,
+    frame: usize,
+}
+
+/// The one clock driving every tileset animation.
+///
+/// Since all tiles of a given Kind share the same frame, animating the map is
+/// only a matter of advancing one track per Kind, however many tiles there are.
+#[derive(Resource)]
+pub struct AnimationClock {
+    tracks: HashMap<Kind, AnimationTrack>,
+}
+
+impl AnimationClock {
+    pub fn new<'a>(data: &AnimationsData, kinds: impl Iterator<Item = &'a Kind>) -> Self {
+        return AnimationClock {
+            tracks: kinds
+                .map(|kind| {
+                    let settings = data.get(kind);
+                    (
+                        *kind,
+                        AnimationTrack {
+                            timer: Timer::from_seconds(
+                                settings.frame_duration,
+                                TimerMode::Repeating,
+                            ),
+                            frame_count: settings.frame_count,
+                            frame: 0,
+                        },
+                    )
+                })
+                .collect(),
+        };
+    }
+}
+
+/// Advance the clock, and move the tilesets whose frame changed accordingly.
+pub fn tick_animation_clock(
+    time: Res<Time>,
+    clock: Option<ResMut<AnimationClock>>,
+    tilesets: Option<Res<Tilesets>>,
+    mut materials: ResMut<Assets<TilemapMaterial>>,
+) {
+    let (Some(mut clock), Some(tilesets)) = (clock, tilesets) else {
+        return;
+    };
+
+    for (kind, track) in clock.tracks.iter_mut() {
+        if track.frame_count < 2 {
+            continue;
+        }
+        track.timer.tick(time.delta());
+        if !track.timer.just_finished() {
+            continue;
+        }
+
+        // Several frames may have elapsed if the game lagged behind
+        track.frame =
+            (track.frame + track.timer.times_finished_this_tick() as usize) % track.frame_count;
+        if let Some(tileset) = tilesets.0.get(kind) {
+            if let Some(material) = materials.get_mut(&tileset.material) {
+                material.frame_offset = tileset.get_frame_offset(track.frame);
+            }
+        }
+    }
+}
@@ -1,22 +1,139 @@
-use bevy::{prelude::*, render::camera::ScalingMode};
+use bevy::{
+    prelude::*,
+    render::camera::{CameraUpdateSystem, ScalingMode},
+    transform::TransformSystem,
+};
 use bevy_pancam::{PanCam, PanCamPlugin};
+use bevy_spritesim::mapgen::{config::MapGenConfig, SPRITE_SIZE};
 
+use super::camera_controls::{center_camera, pan_camera, zoom_camera, CameraControls};
 use super::constants::{WINDOW_PHYSICAL_HEIGHT, WINDOW_PHYSICAL_WIDTH};
 
+/// How much of the world (in pixels) the camera shows vertically when not
+/// zoomed
+const VIEWPORT_HEIGHT: f32 = 1000.;
+/// How far the camera can zoom in (a tenth of the default view)
+const MIN_SCALE: f32 = 0.1;
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(PanCamPlugin::default());
+        app.add_plugins(PanCamPlugin);
         app.add_systems(Startup, setup_camera);
+        app.init_resource::<CameraControls>();
+        app.add_systems(
+            Update,
+            (update_camera_bounds, pan_camera, zoom_camera, center_camera),
+        );
+        // Once everything (PanCam, the minimap, etc.) had a chance to move the
+        // camera, and the visible area was computed
+        app.add_systems(
+            PostUpdate,
+            keep_camera_in_bounds
+                .after(CameraUpdateSystem)
+                .before(TransformSystem::TransformPropagate),
+        );
     }
 }
 
-fn setup_camera(mut commands: Commands) {
+/// The area (in world coordinates) covered by the map, whose tiles are
+/// centered on their real coordinates
+pub fn get_map_bounds(config: &MapGenConfig) -> Rect {
+    let half_tile = SPRITE_SIZE / 2.;
+    return Rect::new(
+        -half_tile,
+        -half_tile,
+        config.width as f32 * SPRITE_SIZE + half_tile,
+        config.height as f32 * SPRITE_SIZE + half_tile,
+    );
+}
+
+/// A PanCam that cannot pan or zoom out past the edges of the map
+fn get_bounded_pancam(config: &MapGenConfig) -> PanCam {
+    let bounds = get_map_bounds(config);
+    return PanCam {
+        min_x: Some(bounds.min.x),
+        max_x: Some(bounds.max.x),
+        min_y: Some(bounds.min.y),
+        max_y: Some(bounds.max.y),
+        min_scale: MIN_SCALE,
+        // Zooming out stops once the whole height of the map is in view
+        max_scale: Some((bounds.height() / VIEWPORT_HEIGHT).max(MIN_SCALE)),
+        ..default()
+    };
+}
+
+fn setup_camera(mut commands: Commands, config: Res<MapGenConfig>) {
     // Configure Camera that can be panned and zoomed with the mouse
-    let mut cam = Camera2dBundle::default();
-    cam.transform =
-        Transform::from_xyz(WINDOW_PHYSICAL_WIDTH / 2., WINDOW_PHYSICAL_HEIGHT / 2., 0.);
-    cam.projection.scaling_mode = ScalingMode::FixedVertical(1000.);
-    commands.spawn((cam, PanCam::default()));
+    let cam = Camera2dBundle {
+        transform: Transform::from_xyz(WINDOW_PHYSICAL_WIDTH / 2., WINDOW_PHYSICAL_HEIGHT / 2., 0.),
+        projection: OrthographicProjection {
+            scaling_mode: ScalingMode::FixedVertical(VIEWPORT_HEIGHT),
+            ..Camera2dBundle::default().projection
+        },
+        ..default()
+    };
+    commands.spawn((cam, get_bounded_pancam(&config)));
+}
+
+/// Follow the size of the map, which may change (when a map is loaded for
+/// instance). Only the bounds are updated, since other systems (the editor,
+/// the minimap) tweak the rest of PanCam.
+fn update_camera_bounds(config: Res<MapGenConfig>, mut cameras: Query<&mut PanCam>) {
+    if !config.is_changed() {
+        return;
+    }
+
+    let bounded = get_bounded_pancam(&config);
+    for mut pancam in cameras.iter_mut() {
+        pancam.min_x = bounded.min_x;
+        pancam.max_x = bounded.max_x;
+        pancam.min_y = bounded.min_y;
+        pancam.max_y = bounded.max_y;
+        pancam.min_scale = bounded.min_scale;
+        pancam.max_scale = bounded.max_scale;
+    }
+}
+
+/// Bring the view back within the bounds of PanCam, which only enforces them
+/// on its own moves. Maps smaller than the view are centered.
+fn keep_camera_in_bounds(
+    mut cameras: Query<(&PanCam, &mut OrthographicProjection, &mut Transform)>,
+) {
+    for (pancam, mut projection, mut transform) in cameras.iter_mut() {
+        let mut half_view = projection.area.size() / 2.;
+        if let Some(max_scale) = pancam.max_scale {
+            if projection.scale > max_scale {
+                // The area will only shrink on the next frame
+                half_view *= max_scale / projection.scale;
+                projection.scale = max_scale;
+            }
+        }
+
+        let clamp = |position: f32, min: Option<f32>, max: Option<f32>, half_view: f32| {
+            return match (min, max) {
+                (Some(min), Some(max)) if max - min < 2. * half_view => (min + max) / 2.,
+                _ => position
+                    .max(min.map_or(f32::MIN, |min| min + half_view))
+                    .min(max.map_or(f32::MAX, |max| max - half_view)),
+            };
+        };
+        let x = clamp(
+            transform.translation.x,
+            pancam.min_x,
+            pancam.max_x,
+            half_view.x,
+        );
+        let y = clamp(
+            transform.translation.y,
+            pancam.min_y,
+            pancam.max_y,
+            half_view.y,
+        );
+        if (x, y) != (transform.translation.x, transform.translation.y) {
+            transform.translation.x = x;
+            transform.translation.y = y;
+        }
+    }
 }
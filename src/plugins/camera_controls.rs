@@ -0,0 +1,208 @@
+use bevy::{input::gamepad::GamepadButtonType, prelude::*, window::PrimaryWindow};
+use bevy_pancam::PanCam;
+use bevy_spritesim::mapgen::config::MapGenConfig;
+
+use super::camera::get_map_bounds;
+
+/// Key (and gamepad button) bindings of the camera, along with how fast it
+/// moves. Insert this resource in the App to change them.
+#[derive(Resource, Clone, Debug)]
+pub struct CameraControls {
+    pub pan_up: Vec<KeyCode>,
+    pub pan_down: Vec<KeyCode>,
+    pub pan_left: Vec<KeyCode>,
+    pub pan_right: Vec<KeyCode>,
+    pub zoom_in: Vec<KeyCode>,
+    pub zoom_out: Vec<KeyCode>,
+    /// Center the view on the map
+    pub center: Vec<KeyCode>,
+    pub center_buttons: Vec<GamepadButtonType>,
+    /// Top panning speed, in view heights per second
+    pub pan_speed: f32,
+    /// How long (in seconds) panning takes to reach its top speed
+    pub acceleration_time: f32,
+    /// How much the view is zoomed in (or out) per second
+    pub zoom_speed: f32,
+    /// How close (in logical pixels) to the window border the cursor must be
+    /// for the view to scroll, 0 disables edge scrolling
+    pub edge_scroll_margin: f32,
+    /// Gamepad stick positions closer to the center than this are ignored
+    pub stick_dead_zone: f32,
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        return CameraControls {
+            pan_up: vec![KeyCode::W, KeyCode::Up],
+            pan_down: vec![KeyCode::S, KeyCode::Down],
+            pan_left: vec![KeyCode::A, KeyCode::Left],
+            pan_right: vec![KeyCode::D, KeyCode::Right],
+            // «+» is typed with «Shift» and «=» on most layouts
+            zoom_in: vec![KeyCode::Plus, KeyCode::Equals, KeyCode::NumpadAdd],
+            zoom_out: vec![KeyCode::Minus, KeyCode::NumpadSubtract],
+            center: vec![KeyCode::Home, KeyCode::C],
+            center_buttons: vec![GamepadButtonType::Select],
+            pan_speed: 1.,
+            acceleration_time: 0.4,
+            zoom_speed: 2.,
+            edge_scroll_margin: 12.,
+            stick_dead_zone: 0.15,
+        };
+    }
+}
+
+/// Position of a stick, summed over every connected gamepad (except the ones
+/// whose stick is within the dead zone)
+fn get_stick_position(
+    axes: &Axis<GamepadAxis>,
+    (x_axis, y_axis): (GamepadAxisType, GamepadAxisType),
+    dead_zone: f32,
+) -> Vec2 {
+    let mut gamepads: Vec<Gamepad> = axes.devices().map(|axis| axis.gamepad).collect();
+    gamepads.sort_by_key(|gamepad| gamepad.id);
+    gamepads.dedup();
+
+    return gamepads
+        .into_iter()
+        .map(|gamepad| {
+            let axis = |axis_type| {
+                return axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.);
+            };
+            Vec2::new(axis(x_axis), axis(y_axis))
+        })
+        .filter(|position| position.length() >= dead_zone)
+        .sum();
+}
+
+/// Pan with the keyboard, the left stick of gamepads and by moving the cursor
+/// to the window borders, speeding up while the move goes on
+pub fn pan_camera(
+    keys: Res<Input<KeyCode>>,
+    axes: Res<Axis<GamepadAxis>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    controls: Res<CameraControls>,
+    time: Res<Time>,
+    mut cameras: Query<(&PanCam, &OrthographicProjection, &mut Transform)>,
+    mut acceleration: Local<f32>,
+) {
+    let mut direction = Vec2::ZERO;
+    let key_direction = |bindings: &Vec<KeyCode>| {
+        return if keys.any_pressed(bindings.iter().copied()) {
+            1.
+        } else {
+            0.
+        };
+    };
+    direction.x += key_direction(&controls.pan_right) - key_direction(&controls.pan_left);
+    direction.y += key_direction(&controls.pan_up) - key_direction(&controls.pan_down);
+
+    direction += get_stick_position(
+        &axes,
+        (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+        controls.stick_dead_zone,
+    );
+
+    // Window positions go downward while world positions go upward
+    let edge_direction = windows
+        .get_single()
+        .ok()
+        .filter(|window| window.focused && controls.edge_scroll_margin > 0.)
+        .and_then(|window| {
+            let cursor = window.cursor_position()?;
+            let margin = controls.edge_scroll_margin;
+            let edge = |position: f32, size: f32| {
+                return if position < margin {
+                    -1.
+                } else if position > size - margin {
+                    1.
+                } else {
+                    0.
+                };
+            };
+            Some(Vec2::new(
+                edge(cursor.x, window.width()),
+                -edge(cursor.y, window.height()),
+            ))
+        })
+        .unwrap_or(Vec2::ZERO);
+
+    if direction == Vec2::ZERO && edge_direction == Vec2::ZERO {
+        *acceleration = 0.;
+        return;
+    }
+    *acceleration = (*acceleration + time.delta_seconds() / controls.acceleration_time).min(1.);
+
+    for (pancam, projection, mut transform) in cameras.iter_mut() {
+        // Edge scrolling is off while PanCam is, so that the cursor can reach
+        // the UI along the borders (like the minimap)
+        let direction = if pancam.enabled {
+            direction + edge_direction
+        } else {
+            direction
+        };
+        let step = direction.clamp_length_max(1.)
+            * controls.pan_speed
+            * projection.area.height()
+            * *acceleration
+            * time.delta_seconds();
+        transform.translation += step.extend(0.);
+    }
+}
+
+/// Zoom with the keyboard and the right stick of gamepads
+pub fn zoom_camera(
+    keys: Res<Input<KeyCode>>,
+    axes: Res<Axis<GamepadAxis>>,
+    controls: Res<CameraControls>,
+    time: Res<Time>,
+    mut cameras: Query<(&PanCam, &mut OrthographicProjection)>,
+) {
+    let mut zoom = 0.;
+    if keys.any_pressed(controls.zoom_in.iter().copied()) {
+        zoom += 1.;
+    }
+    if keys.any_pressed(controls.zoom_out.iter().copied()) {
+        zoom -= 1.;
+    }
+    zoom += get_stick_position(
+        &axes,
+        (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        controls.stick_dead_zone,
+    )
+    .y;
+    if zoom == 0. {
+        return;
+    }
+
+    // Zooming out past the maximum scale is undone along with the bounds
+    for (pancam, mut projection) in cameras.iter_mut() {
+        let factor = controls.zoom_speed.powf(-zoom * time.delta_seconds());
+        projection.scale = (projection.scale * factor).max(pancam.min_scale);
+    }
+}
+
+/// Center the view on the map
+pub fn center_camera(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    controls: Res<CameraControls>,
+    config: Res<MapGenConfig>,
+    mut cameras: Query<&mut Transform, With<PanCam>>,
+) {
+    let button_pressed = gamepads.iter().any(|gamepad| {
+        controls
+            .center_buttons
+            .iter()
+            .any(|button_type| buttons.just_pressed(GamepadButton::new(gamepad, *button_type)))
+    });
+    if !keys.any_just_pressed(controls.center.iter().copied()) && !button_pressed {
+        return;
+    }
+
+    let center = get_map_bounds(&config).center();
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+use bevy_pancam::PanCam;
+use bevy_spritesim::mapgen::{
+    painting::{can_paint, paint_tile},
+    DevelopmentKind, FeatureKind, Kind, SpecialKind, TerrainKind, TileLayers,
+};
+
+use super::map::WorldMap;
+use super::picking::HoveredTile;
+
+/// Every Kind the brush can paint, in the order they are cycled through
+const PALETTE: [Kind; 23] = [
+    Kind::TKind(TerrainKind::Plain),
+    Kind::TKind(TerrainKind::Desert),
+    Kind::TKind(TerrainKind::Grassland),
+    Kind::TKind(TerrainKind::Savanna),
+    Kind::TKind(TerrainKind::Jungle),
+    Kind::TKind(TerrainKind::Swamp),
+    Kind::TKind(TerrainKind::Tundra),
+    Kind::TKind(TerrainKind::Snow),
+    Kind::FKind(FeatureKind::Ocean),
+    Kind::FKind(FeatureKind::DeepOcean),
+    Kind::FKind(FeatureKind::Lake),
+    Kind::FKind(FeatureKind::Hill),
+    Kind::FKind(FeatureKind::Forest),
+    Kind::FKind(FeatureKind::River),
+    Kind::SKind(SpecialKind::Corn),
+    Kind::SKind(SpecialKind::Lumber),
+    Kind::SKind(SpecialKind::Fish),
+    Kind::SKind(SpecialKind::FreshwaterFish),
+    Kind::SKind(SpecialKind::Mountain),
+    Kind::DKind(DevelopmentKind::Road),
+    Kind::DKind(DevelopmentKind::Farmland),
+    Kind::DKind(DevelopmentKind::Irrigation),
+    Kind::DKind(DevelopmentKind::Mine),
+];
+const MAX_BRUSH_SIZE: i32 = 8;
+/// How many edits can be undone
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrushShape {
+    Square,
+    Circle,
+}
+
+/// What gets painted, and where around the hovered tile
+#[derive(Clone, Copy, Debug)]
+pub struct Brush {
+    /// Index of the painted Kind in the palette
+    palette_index: usize,
+    /// 1 paints a single tile, 2 the tile and its neighbours, and so on
+    pub size: i32,
+    pub shape: BrushShape,
+}
+
+impl Brush {
+    pub fn get_kind(&self) -> Kind {
+        return PALETTE[self.palette_index];
+    }
+
+    /// Coordinates of the tiles covered by the brush
+    pub fn get_tiles(&self, center: &(i32, i32)) -> Vec<(i32, i32)> {
+        let reach = self.size - 1;
+        let mut tiles = Vec::new();
+        for w in -reach..=reach {
+            for h in -reach..=reach {
+                if self.shape == BrushShape::Circle
+                    && (w * w + h * h) as f32 > (self.size as f32 - 0.5).powi(2)
+                {
+                    continue;
+                }
+                tiles.push((center.0 + w, center.1 + h));
+            }
+        }
+        return tiles;
+    }
+}
+
+/// The layers of the tiles touched by an edit, before and after it, in the
+/// order they were painted
+#[derive(Default)]
+struct Edit(Vec<((i32, i32), TileLayers, TileLayers)>);
+
+/// State of the map editor
+#[derive(Resource)]
+pub struct Editor {
+    pub enabled: bool,
+    pub brush: Brush,
+    /// Edits that can be undone, the latest last
+    history: Vec<Edit>,
+    /// Edits that were undone and can be redone, the latest undone last
+    undone: Vec<Edit>,
+    /// The edit being painted while the mouse button is held
+    stroke: Option<Edit>,
+    /// Last tile the brush was applied on during the stroke
+    last_painted: Option<(i32, i32)>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        return Editor {
+            enabled: false,
+            brush: Brush {
+                palette_index: 0,
+                size: 1,
+                shape: BrushShape::Square,
+            },
+            history: Vec::new(),
+            undone: Vec::new(),
+            stroke: None,
+            last_painted: None,
+        };
+    }
+}
+
+#[derive(Component)]
+struct EditorText;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_systems(Startup, setup_editor)
+            .add_systems(
+                Update,
+                (
+                    toggle_editor,
+                    change_brush,
+                    paint_with_brush,
+                    undo_edits,
+                    update_editor_text,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn setup_editor(mut commands: Commands) {
+    commands.spawn((
+        EditorText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 14.,
+                color: Color::rgb(1., 1., 0.88),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.18, 0.13, 0.18, 0.85)),
+    ));
+}
+
+/// Enter or leave the editor with «E». While editing, the left mouse button
+/// paints instead of moving the camera.
+fn toggle_editor(
+    keys: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    mut cameras: Query<&mut PanCam>,
+) {
+    if !keys.just_pressed(KeyCode::E) {
+        return;
+    }
+
+    editor.enabled = !editor.enabled;
+    for mut pancam in cameras.iter_mut() {
+        if editor.enabled {
+            pancam
+                .grab_buttons
+                .retain(|button| *button != MouseButton::Left);
+        } else if !pancam.grab_buttons.contains(&MouseButton::Left) {
+            pancam.grab_buttons.push(MouseButton::Left);
+        }
+    }
+}
+
+/// «,» and «.» cycle through the palette, «[» and «]» change the brush size
+/// and «B» its shape
+fn change_brush(keys: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+    let brush_keys = [
+        KeyCode::Period,
+        KeyCode::Comma,
+        KeyCode::BracketRight,
+        KeyCode::BracketLeft,
+        KeyCode::B,
+    ];
+    if !editor.enabled || !keys.any_just_pressed(brush_keys) {
+        return;
+    }
+
+    let brush = &mut editor.brush;
+    if keys.just_pressed(KeyCode::Period) {
+        brush.palette_index = (brush.palette_index + 1) % PALETTE.len();
+    }
+    if keys.just_pressed(KeyCode::Comma) {
+        brush.palette_index = (brush.palette_index + PALETTE.len() - 1) % PALETTE.len();
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        brush.size = (brush.size + 1).min(MAX_BRUSH_SIZE);
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        brush.size = (brush.size - 1).max(1);
+    }
+    if keys.just_pressed(KeyCode::B) {
+        brush.shape = match brush.shape {
+            BrushShape::Square => BrushShape::Circle,
+            BrushShape::Circle => BrushShape::Square,
+        };
+    }
+}
+
+/// Apply a list of tile layers to the map
+fn apply_layers<'a>(
+    map: &mut WorldMap,
+    changes: impl Iterator<Item = (&'a (i32, i32), &'a TileLayers)>,
+) {
+    for (coordinates, layers) in changes {
+        if let Some(tile) = map.tile_at_mut(coordinates) {
+            tile.layers = layers.clone();
+        }
+    }
+    map.update_water_bodies();
+}
+
+/// Paint the hovered tiles while the left mouse button is held. A whole stroke
+/// makes a single edit.
+fn paint_with_brush(
+    buttons: Res<Input<MouseButton>>,
+    hovered_tile: Res<HoveredTile>,
+    map: Option<ResMut<WorldMap>>,
+    mut editor: ResMut<Editor>,
+) {
+    let Some(mut map) = map else {
+        return;
+    };
+
+    if editor.enabled && buttons.pressed(MouseButton::Left) {
+        let Some(center) = hovered_tile.0 else {
+            return;
+        };
+        if editor.last_painted == Some(center) {
+            return;
+        }
+
+        let brush = editor.brush;
+        let mut stroke = editor.stroke.take().unwrap_or_default();
+        let kind = brush.get_kind();
+        for coordinates in brush.get_tiles(&center) {
+            // Tiles that cannot hold the Kind are simply left untouched, and
+            // must not be reported as changed
+            if !map
+                .get(&coordinates)
+                .is_some_and(|tile| can_paint(tile, kind))
+            {
+                continue;
+            }
+            let Some(tile) = map.tile_at_mut(&coordinates) else {
+                continue;
+            };
+            if let Some(previous_layers) = paint_tile(tile, kind) {
+                stroke
+                    .0
+                    .push((coordinates, previous_layers, tile.layers.clone()));
+            }
+        }
+        editor.stroke = Some(stroke);
+        editor.last_painted = Some(center);
+        return;
+    }
+
+    // The stroke is over
+    if editor.stroke.is_none() {
+        return;
+    }
+    editor.last_painted = None;
+    if let Some(stroke) = editor.stroke.take() {
+        if stroke.0.is_empty() {
+            return;
+        }
+        map.update_water_bodies();
+        editor.history.push(stroke);
+        if editor.history.len() > HISTORY_LIMIT {
+            editor.history.remove(0);
+        }
+        editor.undone.clear();
+    }
+}
+
+/// Undo the last edit with «Ctrl+Z», and redo it with «Ctrl+Y» (or
+/// «Ctrl+Shift+Z»)
+fn undo_edits(
+    keys: Res<Input<KeyCode>>,
+    map: Option<ResMut<WorldMap>>,
+    mut editor: ResMut<Editor>,
+) {
+    let Some(mut map) = map else {
+        return;
+    };
+    if !editor.enabled || editor.stroke.is_some() {
+        return;
+    }
+
+    let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let undo = control && !shift && keys.just_pressed(KeyCode::Z);
+    let redo = control && (keys.just_pressed(KeyCode::Y) || shift && keys.just_pressed(KeyCode::Z));
+
+    if undo {
+        if let Some(edit) = editor.history.pop() {
+            // Tiles may have been painted several times in a stroke, so they
+            // are restored in reverse order
+            apply_layers(
+                &mut map,
+                edit.0
+                    .iter()
+                    .rev()
+                    .map(|(coordinates, before, _)| (coordinates, before)),
+            );
+            editor.undone.push(edit);
+        }
+    } else if redo {
+        if let Some(edit) = editor.undone.pop() {
+            apply_layers(
+                &mut map,
+                edit.0
+                    .iter()
+                    .map(|(coordinates, _, after)| (coordinates, after)),
+            );
+            editor.history.push(edit);
+        }
+    }
+}
+
+fn update_editor_text(editor: Res<Editor>, mut texts: Query<&mut Text, With<EditorText>>) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = if editor.enabled {
+            format!(
+                "Editor: {:?}, size {}, {:?} ({} to undo, {} to redo)\n\
+                 E: leave | ,/.: kind | [/]: size | B: shape | Ctrl+Z/Ctrl+Y: undo/redo",
+                editor.brush.get_kind(),
+                editor.brush.size,
+                editor.brush.shape,
+                editor.history.len(),
+                editor.undone.len()
+            )
+        } else {
+            "E: edit the map".to_string()
+        };
+    }
+}
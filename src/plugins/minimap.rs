@@ -0,0 +1,210 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    window::PrimaryWindow,
+};
+use bevy_pancam::{PanCam, PanCamSystemSet};
+use bevy_spritesim::{
+    mapgen::{config::MapGenConfig, SPRITE_SIZE},
+    overview::render_overview,
+};
+
+use super::map::WorldMap;
+
+/// The minimap is drawn at one pixel per tile, unless the map is larger than
+/// this (in logical pixels), in which case it is scaled down to fit
+const MINIMAP_MAX_SIZE: f32 = 200.;
+const MINIMAP_MARGIN: f32 = 8.;
+const VIEWPORT_COLOR: Color = Color::rgb(1., 1., 0.88);
+
+/// The overview image the minimap displays
+#[derive(Resource)]
+struct MinimapImage(Handle<Image>);
+
+#[derive(Component)]
+struct Minimap;
+
+/// The rectangle showing what the camera sees
+#[derive(Component)]
+struct MinimapViewport;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        // The map size is only known for sure once it was generated or loaded
+        app.add_systems(PostStartup, setup_minimap).add_systems(
+            Update,
+            (
+                update_minimap_image,
+                (toggle_camera_dragging, jump_to_clicked_location)
+                    .chain()
+                    .after(PanCamSystemSet),
+                update_minimap_viewport,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Size (in logical pixels) the minimap is displayed at
+fn get_minimap_size(config: &MapGenConfig) -> Vec2 {
+    let tiles = Vec2::new((config.width + 1) as f32, (config.height + 1) as f32);
+    return tiles * (MINIMAP_MAX_SIZE / tiles.max_element()).min(1.);
+}
+
+fn setup_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    config: Res<MapGenConfig>,
+) {
+    // The image is filled once the map is there
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: (config.width + 1) as u32,
+            height: (config.height + 1) as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    let size = get_minimap_size(&config);
+
+    commands
+        .spawn((
+            Minimap,
+            // Lets the UI tell when the minimap is hovered or clicked
+            Interaction::default(),
+            ImageBundle {
+                image: UiImage::new(image.clone()),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(MINIMAP_MARGIN),
+                    right: Val::Px(MINIMAP_MARGIN),
+                    width: Val::Px(size.x),
+                    height: Val::Px(size.y),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|minimap| {
+            minimap.spawn((
+                MinimapViewport,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(1.)),
+                        ..default()
+                    },
+                    border_color: VIEWPORT_COLOR.into(),
+                    ..default()
+                },
+            ));
+        });
+    commands.insert_resource(MinimapImage(image));
+}
+
+/// Draw the map on the minimap, again whenever it changes
+fn update_minimap_image(
+    map: Option<Res<WorldMap>>,
+    config: Res<MapGenConfig>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(map) = map else {
+        return;
+    };
+    if !map.is_changed() {
+        return;
+    }
+
+    if let Some(image) = images.get_mut(&minimap_image.0) {
+        image.data = render_overview(&map, config.width, config.height).into_raw();
+    }
+}
+
+/// The camera must not be dragged around while the minimap is clicked
+fn toggle_camera_dragging(
+    minimaps: Query<&Interaction, (With<Minimap>, Changed<Interaction>)>,
+    mut cameras: Query<&mut PanCam>,
+) {
+    for interaction in minimaps.iter() {
+        for mut pancam in cameras.iter_mut() {
+            pancam.enabled = *interaction == Interaction::None;
+        }
+    }
+}
+
+/// Center the camera on the location clicked on the minimap (and keep
+/// following the cursor while the button is held)
+fn jump_to_clicked_location(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    minimaps: Query<(&Interaction, &Node, &GlobalTransform), With<Minimap>>,
+    config: Res<MapGenConfig>,
+    mut cameras: Query<&mut Transform, With<PanCam>>,
+) {
+    let Some(cursor) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+
+    for (interaction, node, transform) in minimaps.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        // Position of the cursor on the minimap, from 0 to 1 (top to bottom)
+        let top_left = transform.translation().truncate() - node.size() / 2.;
+        let ratio = ((cursor - top_left) / node.size()).clamp(Vec2::ZERO, Vec2::ONE);
+        // Tiles are centered on their real coordinates
+        let tiles = Vec2::new((config.width + 1) as f32, (config.height + 1) as f32);
+        let target = (Vec2::new(ratio.x, 1. - ratio.y) * tiles - 0.5) * SPRITE_SIZE;
+        for mut camera_transform in cameras.iter_mut() {
+            camera_transform.translation.x = target.x;
+            camera_transform.translation.y = target.y;
+        }
+    }
+}
+
+/// Frame the part of the map the camera sees on the minimap
+fn update_minimap_viewport(
+    cameras: Query<(Ref<Transform>, Ref<OrthographicProjection>), With<PanCam>>,
+    config: Res<MapGenConfig>,
+    minimaps: Query<Ref<Node>, With<Minimap>>,
+    mut viewports: Query<&mut Style, With<MinimapViewport>>,
+) {
+    let (Ok((camera_transform, projection)), Ok(node)) =
+        (cameras.get_single(), minimaps.get_single())
+    else {
+        return;
+    };
+    // Changing the style triggers a new UI layout, so only do it when needed
+    if !camera_transform.is_changed() && !projection.is_changed() && !node.is_changed() {
+        return;
+    }
+
+    // Visible world area, converted to tiles then to minimap pixels (whose
+    // rows go downward)
+    let tiles = Vec2::new((config.width + 1) as f32, (config.height + 1) as f32);
+    let scale = node.size() / tiles;
+    let center = camera_transform.translation.truncate();
+    let to_minimap = |world: Vec2| {
+        let tile = world / SPRITE_SIZE + 0.5;
+        return Vec2::new(tile.x, tiles.y - tile.y) * scale;
+    };
+    let top_left = to_minimap(center + Vec2::new(projection.area.min.x, projection.area.max.y));
+    let bottom_right = to_minimap(center + Vec2::new(projection.area.max.x, projection.area.min.y));
+
+    for mut style in viewports.iter_mut() {
+        style.left = Val::Px(top_left.x);
+        style.top = Val::Px(top_left.y);
+        style.width = Val::Px(bottom_right.x - top_left.x);
+        style.height = Val::Px(bottom_right.y - top_left.y);
+    }
+}
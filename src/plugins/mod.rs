@@ -1,3 +1,11 @@
+pub mod animation;
 pub mod camera;
+pub mod camera_controls;
 pub mod constants;
+pub mod editor;
 pub mod map;
+pub mod minimap;
+pub mod picking;
+pub mod settlement;
+pub mod tilemap;
+pub mod unit;
@@ -0,0 +1,309 @@
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow};
+use bevy_spritesim::mapgen::{query::MapQuery, Kind, Layer, SPRITE_SIZE};
+
+use super::map::{TileEntities, WorldMap};
+use super::settlement::Settlement;
+use super::unit::{MoveUnit, Unit};
+
+/// The hovered tile highlight is drawn above the map, but under units
+const HIGHLIGHT_ZINDEX: f32 = 9.;
+const HIGHLIGHT_COLOR: Color = Color::rgba(1., 1., 0.88, 0.35);
+/// How far (in logical pixels) the cursor may move between a press and a
+/// release for them to be a click rather than a drag of the camera
+const CLICK_TOLERANCE: f32 = 4.;
+
+/// The tile under the cursor, if any
+#[derive(Resource, Default, Debug)]
+pub struct HoveredTile(pub Option<(i32, i32)>);
+
+/// The tile that was last clicked, if any
+#[derive(Resource, Default, Debug)]
+pub struct SelectedTile(pub Option<(i32, i32)>);
+
+/// A tile was clicked (rather than the camera dragged) with a mouse button
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TileClicked {
+    pub button: MouseButton,
+    pub coordinates: (i32, i32),
+}
+
+/// Where each mouse button was pressed, to tell clicks from drags
+#[derive(Resource, Default)]
+struct PressPositions(HashMap<MouseButton, Vec2>);
+
+#[derive(Component)]
+struct TileHighlight;
+
+#[derive(Component)]
+struct TileInfoText;
+
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredTile>()
+            .init_resource::<SelectedTile>()
+            .init_resource::<PressPositions>()
+            .add_event::<TileClicked>()
+            .add_systems(Startup, setup_picking)
+            .add_systems(
+                Update,
+                (
+                    (update_hovered_tile, detect_tile_clicks).chain(),
+                    (highlight_hovered_tile, select_tile, order_selected_units),
+                    update_tile_info,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Map coordinates of the tile drawn at a given world position (tiles are
+/// centered on their real coordinates)
+pub fn get_tile_at_position(position: Vec2) -> (i32, i32) {
+    return (
+        (position.x / SPRITE_SIZE).round() as i32,
+        (position.y / SPRITE_SIZE).round() as i32,
+    );
+}
+
+/// The name of a Kind, without the name of its layer
+fn get_kind_name(kind: &Kind) -> String {
+    return match kind {
+        Kind::TKind(kind) => format!("{:?}", kind),
+        Kind::FKind(kind) => format!("{:?}", kind),
+        Kind::SKind(kind) => format!("{:?}", kind),
+        Kind::DKind(kind) => format!("{:?}", kind),
+        Kind::StKind(kind) => format!("{:?}", kind),
+    };
+}
+
+fn setup_picking(mut commands: Commands) {
+    commands.spawn((
+        TileHighlight,
+        SpriteBundle {
+            sprite: Sprite {
+                color: HIGHLIGHT_COLOR,
+                custom_size: Some(Vec2::splat(SPRITE_SIZE)),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                left: Val::Px(8.),
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgba(0.18, 0.13, 0.18, 0.85).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|panel| {
+            panel.spawn((
+                TileInfoText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.,
+                        color: Color::rgb(1., 1., 0.88),
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+/// Convert the cursor position through the camera into map coordinates
+fn update_hovered_tile(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    map: Option<Res<WorldMap>>,
+    interactions: Query<&Interaction>,
+    mut hovered_tile: ResMut<HoveredTile>,
+) {
+    let (Ok(window), Ok((camera, camera_transform)), Some(map)) =
+        (windows.get_single(), cameras.get_single(), map)
+    else {
+        return;
+    };
+
+    // Tiles under interactive UI nodes (like the minimap) cannot be hovered
+    let over_ui = interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let coordinates = window
+        .cursor_position()
+        .filter(|_| !over_ui)
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .map(get_tile_at_position)
+        .filter(|coordinates| map.tile_at(coordinates).is_some());
+    if hovered_tile.0 != coordinates {
+        hovered_tile.0 = coordinates;
+    }
+}
+
+fn highlight_hovered_tile(
+    hovered_tile: Res<HoveredTile>,
+    mut highlights: Query<(&mut Transform, &mut Visibility), With<TileHighlight>>,
+) {
+    if !hovered_tile.is_changed() {
+        return;
+    }
+
+    for (mut transform, mut visibility) in highlights.iter_mut() {
+        match hovered_tile.0 {
+            Some(coordinates) => {
+                transform.translation = Vec3::new(
+                    coordinates.0 as f32 * SPRITE_SIZE,
+                    coordinates.1 as f32 * SPRITE_SIZE,
+                    HIGHLIGHT_ZINDEX,
+                );
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+/// Turn mouse buttons released close to where they were pressed into clicks
+/// on the hovered tile
+fn detect_tile_clicks(
+    buttons: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    hovered_tile: Res<HoveredTile>,
+    mut press_positions: ResMut<PressPositions>,
+    mut clicks: EventWriter<TileClicked>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    for button in buttons.get_just_pressed() {
+        if let Some(position) = window.cursor_position() {
+            press_positions.0.insert(*button, position);
+        }
+    }
+    for button in buttons.get_just_released() {
+        let is_click = match (press_positions.0.remove(button), window.cursor_position()) {
+            (Some(pressed), Some(released)) => pressed.distance(released) <= CLICK_TOLERANCE,
+            _ => false,
+        };
+        if let (true, Some(coordinates)) = (is_click, hovered_tile.0) {
+            clicks.send(TileClicked {
+                button: *button,
+                coordinates,
+            });
+        }
+    }
+}
+
+/// Select a tile on left click, or deselect it when clicking it again
+fn select_tile(mut clicks: EventReader<TileClicked>, mut selected_tile: ResMut<SelectedTile>) {
+    for click in clicks.read() {
+        if click.button != MouseButton::Left {
+            continue;
+        }
+        selected_tile.0 = if selected_tile.0 == Some(click.coordinates) {
+            None
+        } else {
+            Some(click.coordinates)
+        };
+    }
+}
+
+/// Send the units standing on the selected tile to the tile right clicked
+fn order_selected_units(
+    mut clicks: EventReader<TileClicked>,
+    selected_tile: Res<SelectedTile>,
+    tile_entities: Res<TileEntities>,
+    units: Query<Entity, With<Unit>>,
+    mut orders: EventWriter<MoveUnit>,
+) {
+    let Some(selected) = selected_tile.0 else {
+        clicks.clear();
+        return;
+    };
+
+    for click in clicks.read() {
+        if click.button != MouseButton::Right {
+            continue;
+        }
+        for unit in units.iter_many(tile_entities.get(&selected)) {
+            orders.send(MoveUnit {
+                unit,
+                destination: click.coordinates,
+            });
+        }
+    }
+}
+
+/// List what stands on the selected tile in the info panel, keeping it up to
+/// date as the map changes
+fn update_tile_info(
+    selected_tile: Res<SelectedTile>,
+    map: Option<Res<WorldMap>>,
+    tile_entities: Res<TileEntities>,
+    settlements: Query<&Settlement>,
+    units: Query<&Unit>,
+    mut texts: Query<(&mut Text, &Parent), With<TileInfoText>>,
+    mut panels: Query<&mut Visibility, With<Node>>,
+) {
+    let Some(map) = map else {
+        return;
+    };
+    if !selected_tile.is_changed() && !map.is_changed() && !tile_entities.is_changed() {
+        return;
+    }
+
+    for (mut text, parent) in texts.iter_mut() {
+        let Ok(mut visibility) = panels.get_mut(parent.get()) else {
+            continue;
+        };
+        let Some((coordinates, tile)) = selected_tile
+            .0
+            .and_then(|coordinates| Some((coordinates, map.tile_at(&coordinates)?)))
+        else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        let mut lines = vec![format!("Tile {:?}", coordinates)];
+        for layer in Layer::ALL {
+            lines.push(format!(
+                "{:?}: {}",
+                layer,
+                tile.layers
+                    .get(&layer)
+                    .map(get_kind_name)
+                    .unwrap_or("-".to_string())
+            ));
+        }
+        lines.push(format!(
+            "Elevation: {:.2}, moisture: {:.2}, temperature: {:.2}",
+            tile.climate.elevation, tile.climate.moisture, tile.climate.temperature
+        ));
+        if let Some(water_body) = tile.water_body {
+            lines.push(format!("Water body: {}", water_body));
+        }
+        let entities = tile_entities.get(&coordinates);
+        for settlement in settlements.iter_many(entities) {
+            lines.push(format!(
+                "{} ({:?}, {} people)",
+                settlement.name, settlement.kind, settlement.population
+            ));
+        }
+        for unit in units.iter_many(entities) {
+            lines.push(format!("Unit: {:?}", unit.kind));
+        }
+
+        text.sections[0].value = lines.join("\n");
+        *visibility = Visibility::Visible;
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+use bevy_spritesim::mapgen::{
+    settlement::describe_settlement, Kind, Layer, Map, MapSeed, SettlementKind,
+};
+
+use super::map::TileCoordinates;
+
+/// A settlement standing on the map. Its sprite is drawn as part of the
+/// tilemap, this entity carries everything else about it.
+#[derive(Component, Reflect, Clone, Debug)]
+pub struct Settlement {
+    pub kind: SettlementKind,
+    pub name: String,
+    pub population: u32,
+}
+
+/// Spawn an entity for every settlement of the map
+pub fn spawn_settlements(commands: &mut Commands, map: &Map, map_seed: &MapSeed) {
+    for (coordinates, tile) in map.iter() {
+        let Some(Kind::StKind(kind)) = tile.layers.get(&Layer::Settlement) else {
+            continue;
+        };
+
+        let info = describe_settlement(map_seed, coordinates, kind);
+        commands.spawn((
+            Name::new(info.name.clone()),
+            Settlement {
+                kind: *kind,
+                name: info.name,
+                population: info.population,
+            },
+            TileCoordinates(*coordinates),
+        ));
+    }
+}
@@ -0,0 +1,318 @@
+use bevy::{
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        mesh::Indices,
+        render_resource::{AsBindGroup, PrimitiveTopology, ShaderRef},
+    },
+    sprite::{Material2d, MaterialMesh2dBundle},
+    utils::{HashMap, HashSet},
+};
+use bevy_spritesim::mapgen::{
+    autotile::{get_tiles_to_display, AutotileRules},
+    get_kind_of_tile_layer, Kind, Layer, Map, SPRITE_SIZE,
+};
+
+use super::map::WorldMap;
+
+/// Size (in tiles) of the square regions the map is split into for rendering
+pub const CHUNK_SIZE: i32 = 32;
+
+/// Base tiles for composite tiles must be under all the other layers
+const COMPOSITE_BACKGROUND_ZINDEX: f32 = 0.5;
+
+/// A material that draws a whole chunk layer from a single tileset image.
+///
+/// Animated tilesets are saved as one column of N frames, so moving from
+/// one frame to the other is just a matter of shifting the texture coordinates
+/// vertically, which is done in the shader from `frame_offset`.
+#[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
+pub struct TilemapMaterial {
+    /// Vertical shift (in texture coordinates) of the current animation frame
+    #[uniform(0)]
+    pub frame_offset: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+}
+
+impl Material2d for TilemapMaterial {
+    fn fragment_shader() -> ShaderRef {
+        return "shaders/tilemap.wgsl".into();
+    }
+}
+
+/// Everything needed to draw a Kind: its material and where its sprites are
+/// inside the tileset image.
+pub struct Tileset {
+    pub material: Handle<TilemapMaterial>,
+    /// Position (in pixels) of the first sprite inside the image
+    pub offset: Vec2,
+    /// Size (in pixels) of the whole image
+    pub image_size: Vec2,
+    /// How many sprites a tileset row holds
+    pub columns: usize,
+    /// How many sprites rows an animation frame holds
+    pub rows: usize,
+    /// How to pick sprites depending on the neighbours of a tile (tilesets
+    /// without rules always use their first sprite)
+    pub rules: Option<AutotileRules>,
+}
+
+impl Tileset {
+    /// Vertical shift (in texture coordinates) of a given animation frame
+    pub fn get_frame_offset(&self, frame: usize) -> f32 {
+        return (frame * self.rows) as f32 * SPRITE_SIZE / self.image_size.y;
+    }
+}
+
+/// In-memory map that ties Kind elements with their corresponding Tileset.
+#[derive(Resource, Default)]
+pub struct Tilesets(pub HashMap<Kind, Tileset>);
+
+/// A square region of the Map, drawn as one mesh per layer Kind.
+#[derive(Component, Reflect)]
+pub struct TilemapChunk {
+    pub coordinates: (i32, i32),
+}
+
+/// The entity of every chunk, by chunk coordinates (see `get_chunk_of_tile`)
+#[derive(Resource, Default)]
+pub struct TilemapChunks(pub HashMap<(i32, i32), Entity>);
+
+/// Chunks whose meshes must be rebuilt on the next frame.
+#[derive(Resource, Default)]
+pub struct DirtyChunks(pub HashSet<(i32, i32)>);
+
+impl DirtyChunks {
+    /// Mark a tile as changed. Since the sprites of its 8 neighbours depend
+    /// on it, their chunks are marked as well.
+    pub fn mark_tile(&mut self, coordinates: &(i32, i32)) {
+        for w in -1..=1 {
+            for h in -1..=1 {
+                self.0
+                    .insert(get_chunk_of_tile(&(coordinates.0 + w, coordinates.1 + h)));
+            }
+        }
+    }
+}
+
+/// Coordinates of the chunk a tile belongs to
+pub fn get_chunk_of_tile(coordinates: &(i32, i32)) -> (i32, i32) {
+    return (
+        coordinates.0.div_euclid(CHUNK_SIZE),
+        coordinates.1.div_euclid(CHUNK_SIZE),
+    );
+}
+
+/// Vertices of a chunk layer, waiting to be turned into a Mesh
+#[derive(Default)]
+struct ChunkLayerBuilder {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl ChunkLayerBuilder {
+    /// Add a quad, centered on the tile real coordinates, showing the sprite
+    /// at the given index of the tileset
+    fn push_sprite(&mut self, real_coordinates: (f32, f32), tileset: &Tileset, index: usize) {
+        let half_size = SPRITE_SIZE / 2.;
+        let (x, y) = real_coordinates;
+        let first_vertex = self.positions.len() as u32;
+        self.positions.extend([
+            [x - half_size, y - half_size, 0.],
+            [x + half_size, y - half_size, 0.],
+            [x + half_size, y + half_size, 0.],
+            [x - half_size, y + half_size, 0.],
+        ]);
+
+        // Sprites are read from the first animation frame, the shader
+        // takes care of moving to the other ones
+        let top_left = Vec2::new(
+            tileset.offset.x + (index % tileset.columns) as f32 * SPRITE_SIZE,
+            tileset.offset.y + (index / tileset.columns) as f32 * SPRITE_SIZE,
+        ) / tileset.image_size;
+        let bottom_right = top_left + Vec2::splat(SPRITE_SIZE) / tileset.image_size;
+        self.uvs.extend([
+            [top_left.x, bottom_right.y],
+            [bottom_right.x, bottom_right.y],
+            [bottom_right.x, top_left.y],
+            [top_left.x, top_left.y],
+        ]);
+
+        self.indices.extend([
+            first_vertex,
+            first_vertex + 1,
+            first_vertex + 2,
+            first_vertex,
+            first_vertex + 2,
+            first_vertex + 3,
+        ]);
+    }
+
+    fn build(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        return mesh;
+    }
+}
+
+fn get_zindex_from_kind(kind: &Kind) -> f32 {
+    return match kind {
+        Kind::TKind(_) => 1.,
+        Kind::FKind(_) => 2.,
+        Kind::SKind(_) => 3.,
+        Kind::DKind(_) => 4.,
+        Kind::StKind(_) => 5.,
+    };
+}
+
+/// Compute the meshes of every layer Kind present in a chunk, along with the
+/// z-index they must be drawn at.
+fn build_chunk_meshes(
+    map: &Map,
+    tilesets: &Tilesets,
+    chunk_coordinates: &(i32, i32),
+) -> Vec<(Kind, f32, Mesh)> {
+    // Layers are batched by Kind (since each Kind has its own tileset) and by
+    // whether they are composite backgrounds (since those are drawn under
+    // everything else)
+    let mut builders: HashMap<(Kind, bool), ChunkLayerBuilder> = HashMap::new();
+
+    for w in chunk_coordinates.0 * CHUNK_SIZE..(chunk_coordinates.0 + 1) * CHUNK_SIZE {
+        for h in chunk_coordinates.1 * CHUNK_SIZE..(chunk_coordinates.1 + 1) * CHUNK_SIZE {
+            let Some(tile) = map.get(&(w, h)) else {
+                continue;
+            };
+            for layer in Layer::ALL {
+                if let Some(kind) = get_kind_of_tile_layer(tile, &layer) {
+                    let tileset_indices = get_tiles_to_display(
+                        tile,
+                        map,
+                        &(w, h),
+                        layer,
+                        tilesets
+                            .0
+                            .get(&kind)
+                            .and_then(|tileset| tileset.rules.as_ref()),
+                    );
+
+                    // For composite tiles (like a beach which is part ocean and part plain),
+                    // we can have a second tile to print
+                    if let Some(background_kind) = tileset_indices.1 {
+                        if let Some(tileset) = tilesets.0.get(&background_kind) {
+                            let background_index = tileset
+                                .rules
+                                .as_ref()
+                                .map_or(0, |rules| rules.get_full_index());
+                            builders
+                                .entry((background_kind, true))
+                                .or_default()
+                                .push_sprite(tile.real_coordinates, tileset, background_index);
+                        }
+                    }
+
+                    if let Some(tileset) = tilesets.0.get(&kind) {
+                        builders.entry((kind, false)).or_default().push_sprite(
+                            tile.real_coordinates,
+                            tileset,
+                            tileset_indices.0,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    return builders
+        .into_iter()
+        .map(|((kind, is_background), builder)| {
+            let zindex = if is_background {
+                COMPOSITE_BACKGROUND_ZINDEX
+            } else {
+                get_zindex_from_kind(&kind)
+            };
+            (kind, zindex, builder.build())
+        })
+        .collect();
+}
+
+/// Spawn an (empty) entity for every chunk of the map, and mark them all as
+/// dirty so that their meshes get built on the next frame.
+pub fn spawn_chunks(
+    commands: &mut Commands,
+    dirty_chunks: &mut DirtyChunks,
+    map_width: i32,
+    map_height: i32,
+) {
+    let mut chunks = TilemapChunks::default();
+    for w in 0..=map_width / CHUNK_SIZE {
+        for h in 0..=map_height / CHUNK_SIZE {
+            let entity = commands
+                .spawn((
+                    TilemapChunk {
+                        coordinates: (w, h),
+                    },
+                    SpatialBundle::default(),
+                ))
+                .id();
+            chunks.0.insert((w, h), entity);
+            dirty_chunks.0.insert((w, h));
+        }
+    }
+    commands.insert_resource(chunks);
+}
+
+/// Mark the chunks of the tiles changed on the WorldMap as dirty
+pub fn mark_changed_tiles(map: Option<ResMut<WorldMap>>, mut dirty_chunks: ResMut<DirtyChunks>) {
+    let Some(mut map) = map else {
+        return;
+    };
+    if !map.is_changed() {
+        return;
+    }
+
+    // Forgetting the changed tiles is not a change of the map itself
+    for coordinates in map.bypass_change_detection().take_changed_tiles() {
+        dirty_chunks.mark_tile(&coordinates);
+    }
+}
+
+/// Replace the meshes of every dirty chunk.
+pub fn rebuild_dirty_chunks(
+    mut commands: Commands,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    map: Res<WorldMap>,
+    tilesets: Res<Tilesets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunks: Res<TilemapChunks>,
+) {
+    if dirty_chunks.0.is_empty() {
+        return;
+    }
+
+    for chunk_coordinates in dirty_chunks.0.drain() {
+        // Tiles on the map edges mark chunks outside of the map
+        let Some(entity) = chunks.0.get(&chunk_coordinates).copied() else {
+            continue;
+        };
+
+        let chunk_meshes = build_chunk_meshes(&map, &tilesets, &chunk_coordinates);
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .with_children(|parent| {
+                for (kind, zindex, mesh) in chunk_meshes {
+                    parent.spawn(MaterialMesh2dBundle {
+                        mesh: meshes.add(mesh).into(),
+                        material: tilesets.0.get(&kind).unwrap().material.clone(),
+                        transform: Transform::from_xyz(0., 0., zindex),
+                        ..default()
+                    });
+                }
+            });
+    }
+}
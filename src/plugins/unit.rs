@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_spritesim::mapgen::{
+    pathfinding::{find_path, Connectivity, MovementCosts},
+    query::MapQuery,
+    unit::{can_enter, check_position, check_step, MoveError},
+    Map, SettlementKind, UnitKind, SPRITE_SIZE,
+};
+
+use super::map::{TileCoordinates, WorldMap};
+use super::settlement::Settlement;
+
+/// Sprites of every Unit Kind, side by side
+const UNITS_PATH: &str = "sprites/unit/units.png";
+/// Units are drawn above every layer of the map
+const UNIT_ZINDEX: f32 = 10.;
+/// In tiles per second
+const UNIT_SPEED: f32 = 4.;
+
+/// A unit moving through the map
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+pub struct Unit {
+    pub kind: UnitKind,
+}
+
+/// Tiles a unit still has to go through, in order.
+///
+/// The unit keeps the coordinates of the tile it leaves until it reaches the
+/// next one, while its sprite slides from one to the other.
+#[derive(Component, Default, Debug)]
+pub struct UnitPath {
+    steps: VecDeque<(i32, i32)>,
+    /// How far (from 0 to 1) the unit went toward the next step
+    progress: f32,
+}
+
+/// Ask a unit to move to a tile (along the cheapest path), after the steps it
+/// was already given.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MoveUnit {
+    pub unit: Entity,
+    pub destination: (i32, i32),
+}
+
+/// The texture atlas every unit is drawn from
+#[derive(Resource)]
+struct UnitSprites(Handle<TextureAtlas>);
+
+pub struct UnitPlugin;
+
+impl Plugin for UnitPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MoveUnit>()
+            .init_resource::<MovementCosts>()
+            .register_type::<Unit>()
+            .add_systems(Startup, load_unit_sprites)
+            // The map and its settlements are only there once Startup is over
+            .add_systems(PostStartup, spawn_initial_units)
+            .add_systems(Update, (order_units, move_units).chain());
+    }
+}
+
+/// Position of the sprite of a Unit Kind in the texture atlas
+fn get_sprite_index(kind: &UnitKind) -> usize {
+    return match kind {
+        UnitKind::Settler => 0,
+        UnitKind::Worker => 1,
+        UnitKind::Soldier => 2,
+        UnitKind::Cannon => 3,
+        UnitKind::Boat => 4,
+        UnitKind::Ship => 5,
+    };
+}
+
+/// Where the sprite of a unit standing on a tile is drawn
+fn get_unit_translation(map: &Map, coordinates: &(i32, i32)) -> Vec3 {
+    let (x, y) = map
+        .get(coordinates)
+        .map(|tile| tile.real_coordinates)
+        .unwrap_or((
+            coordinates.0 as f32 * SPRITE_SIZE,
+            coordinates.1 as f32 * SPRITE_SIZE,
+        ));
+    return Vec3::new(x, y, UNIT_ZINDEX);
+}
+
+fn load_unit_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let texture_atlas = TextureAtlas::from_grid(
+        asset_server.load(UNITS_PATH),
+        Vec2::splat(SPRITE_SIZE),
+        6,
+        1,
+        None,
+        None,
+    );
+    commands.insert_resource(UnitSprites(texture_atlases.add(texture_atlas)));
+}
+
+/// Spawn a unit on a tile, provided it can stand there
+fn spawn_unit(
+    commands: &mut Commands,
+    sprites: &UnitSprites,
+    map: &Map,
+    kind: UnitKind,
+    coordinates: (i32, i32),
+) -> Result<Entity, MoveError> {
+    check_position(map, &coordinates, &kind)?;
+    return Ok(commands
+        .spawn((
+            Name::new(format!("{:?}", kind)),
+            Unit { kind },
+            TileCoordinates(coordinates),
+            UnitPath::default(),
+            SpriteSheetBundle {
+                texture_atlas: sprites.0.clone(),
+                sprite: TextureAtlasSprite::new(get_sprite_index(&kind)),
+                transform: Transform::from_translation(get_unit_translation(map, &coordinates)),
+                ..default()
+            },
+        ))
+        .id());
+}
+
+/// Every settlement starts with a unit matching its Kind, and coastal ones
+/// with a boat as well (cities can afford an ocean-going ship).
+fn spawn_initial_units(
+    mut commands: Commands,
+    sprites: Res<UnitSprites>,
+    map: Option<Res<WorldMap>>,
+    settlements: Query<(&Settlement, &TileCoordinates)>,
+) {
+    let Some(map) = map else {
+        return;
+    };
+
+    for (settlement, coordinates) in settlements.iter() {
+        let kind = match settlement.kind {
+            SettlementKind::Village => UnitKind::Worker,
+            SettlementKind::Town => UnitKind::Settler,
+            SettlementKind::City => UnitKind::Soldier,
+            SettlementKind::Fort => UnitKind::Cannon,
+        };
+        if let Err(error) = spawn_unit(&mut commands, &sprites, &map, kind, coordinates.0) {
+            warn!("{}", error);
+        }
+
+        let hull = match settlement.kind {
+            SettlementKind::City => UnitKind::Ship,
+            SettlementKind::Village | SettlementKind::Town | SettlementKind::Fort => UnitKind::Boat,
+        };
+        let shore = map
+            .neighbours(&coordinates.0)
+            .into_iter()
+            .find(|(_, tile)| can_enter(tile, &hull))
+            .map(|(neighbour, _)| neighbour);
+        if let Some(shore) = shore {
+            if let Err(error) = spawn_unit(&mut commands, &sprites, &map, hull, shore) {
+                warn!("{}", error);
+            }
+        }
+    }
+}
+
+/// Queue the steps units need to take to reach the tiles they were ordered
+/// to, if there is a way
+fn order_units(
+    mut orders: EventReader<MoveUnit>,
+    map: Option<Res<WorldMap>>,
+    movement_costs: Res<MovementCosts>,
+    mut units: Query<(&Unit, &TileCoordinates, &mut UnitPath)>,
+) {
+    let Some(map) = map else {
+        return;
+    };
+
+    for order in orders.read() {
+        let Ok((unit, coordinates, mut path)) = units.get_mut(order.unit) else {
+            continue;
+        };
+        let from = *path.steps.back().unwrap_or(&coordinates.0);
+        match find_path(
+            &map,
+            &from,
+            &order.destination,
+            &unit.kind,
+            &movement_costs,
+            Connectivity::Eight,
+        ) {
+            // The first step is where the unit already is
+            Some(found) => path.steps.extend(found.steps.into_iter().skip(1)),
+            None => warn!(
+                "{:?} cannot go from {:?} to {:?}",
+                unit.kind, from, order.destination
+            ),
+        }
+    }
+}
+
+/// Slide units toward their next step, and move them to it once reached.
+///
+/// The map may have changed since a unit was given its steps, so it looks for
+/// another way to its destination rather than taking a step it is not allowed
+/// to, and stops where it is if there is none.
+fn move_units(
+    time: Res<Time>,
+    map: Option<Res<WorldMap>>,
+    movement_costs: Res<MovementCosts>,
+    mut units: Query<(&Unit, &mut TileCoordinates, &mut UnitPath, &mut Transform)>,
+) {
+    let Some(map) = map else {
+        return;
+    };
+
+    for (unit, mut coordinates, mut path, mut transform) in units.iter_mut() {
+        let Some(next) = path.steps.front().copied() else {
+            continue;
+        };
+
+        if let Err(error) = check_step(&map, &coordinates.0, &next, &unit.kind) {
+            let destination = *path.steps.back().unwrap();
+            path.progress = 0.;
+            path.steps = match find_path(
+                &map,
+                &coordinates.0,
+                &destination,
+                &unit.kind,
+                &movement_costs,
+                Connectivity::Eight,
+            ) {
+                // The first step is where the unit already is
+                Some(found) => found.steps.into_iter().skip(1).collect(),
+                None => {
+                    warn!("{}, {:?} stops at {:?}", error, unit.kind, coordinates.0);
+                    VecDeque::new()
+                }
+            };
+            transform.translation = get_unit_translation(&map, &coordinates.0);
+            continue;
+        }
+
+        path.progress += UNIT_SPEED * time.delta_seconds();
+        if path.progress >= 1. {
+            coordinates.0 = next;
+            path.steps.pop_front();
+            path.progress = if path.steps.is_empty() {
+                0.
+            } else {
+                path.progress - 1.
+            };
+        }
+
+        let from = get_unit_translation(&map, &coordinates.0);
+        transform.translation = match path.steps.front() {
+            Some(next) => from.lerp(get_unit_translation(&map, next), path.progress),
+            None => from,
+        };
+    }
+}
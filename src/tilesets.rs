@@ -0,0 +1,205 @@
+//! Where the sprites of every Kind are drawn from, shared by the game and the
+//! tools that render maps without a window.
+
+use std::path::PathBuf;
+
+use bevy::{
+    asset::io::file::FileAssetReader,
+    math::{vec2, Vec2},
+};
+
+use crate::mapgen::{
+    autotile::{AutotileRules, AutotileRulesError},
+    DevelopmentKind, FeatureKind, Kind, SettlementKind, SpecialKind, TerrainKind,
+};
+
+/// How many animation frames a tileset holds
+pub const ANIMATION_FRAME_COUNT: usize = 4;
+
+/// Autotile rules of the 7x7 «blob» tilesets
+pub const BLOB_RULES: Option<&str> = Some("autotile/blob_7x7.ron");
+/// Autotile rules of the 16 tiles «cardinal» tilesets
+pub const CARDINAL_RULES: Option<&str> = Some("autotile/cardinal_16.ron");
+/// Autotile rules of the (coastal) ocean tileset (blob, merging into the deep
+/// ocean)
+pub const OCEAN_RULES: Option<&str> = Some("autotile/ocean_7x7.ron");
+/// Autotile rules of the river tileset (cardinal, flowing into the sea)
+pub const RIVER_RULES: Option<&str> = Some("autotile/river_16.ron");
+
+/// Every Kind that has a tileset, along with the image it is drawn from, the
+/// position (in pixels) of its first sprite inside this image and the autotile
+/// rules of its layout (if it has several sprites).
+pub const TILESETS: [(Kind, &str, Vec2, Option<&str>); 27] = [
+    (
+        Kind::FKind(FeatureKind::Forest),
+        "sprites/terrain/forest.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::FKind(FeatureKind::Ocean),
+        "sprites/terrain/ocean.png",
+        Vec2::ZERO,
+        OCEAN_RULES,
+    ),
+    (
+        Kind::FKind(FeatureKind::DeepOcean),
+        "sprites/terrain/deep_ocean.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Plain),
+        "sprites/terrain/plain.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Desert),
+        "sprites/terrain/desert.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Tundra),
+        "sprites/terrain/tundra.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Snow),
+        "sprites/terrain/snow.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Grassland),
+        "sprites/terrain/grassland.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Savanna),
+        "sprites/terrain/savanna.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Jungle),
+        "sprites/terrain/jungle.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::TKind(TerrainKind::Swamp),
+        "sprites/terrain/swamp.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::FKind(FeatureKind::Hill),
+        "sprites/terrain/hill.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::FKind(FeatureKind::Lake),
+        "sprites/terrain/lake.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::FKind(FeatureKind::River),
+        "sprites/terrain/river.png",
+        Vec2::ZERO,
+        RIVER_RULES,
+    ),
+    (
+        Kind::SKind(SpecialKind::Mountain),
+        "sprites/terrain/mountain.png",
+        Vec2::ZERO,
+        BLOB_RULES,
+    ),
+    (
+        Kind::SKind(SpecialKind::Lumber),
+        "sprites/terrain/specials.png",
+        Vec2::ZERO,
+        None,
+    ),
+    (
+        Kind::SKind(SpecialKind::Corn),
+        "sprites/terrain/specials.png",
+        vec2(16., 0.),
+        None,
+    ),
+    (
+        Kind::SKind(SpecialKind::Fish),
+        "sprites/terrain/specials.png",
+        vec2(32., 0.),
+        None,
+    ),
+    (
+        Kind::SKind(SpecialKind::FreshwaterFish),
+        "sprites/terrain/specials.png",
+        vec2(80., 0.),
+        None,
+    ),
+    (
+        Kind::DKind(DevelopmentKind::Road),
+        "sprites/development/road.png",
+        Vec2::ZERO,
+        CARDINAL_RULES,
+    ),
+    (
+        Kind::DKind(DevelopmentKind::Farmland),
+        "sprites/development/developments.png",
+        Vec2::ZERO,
+        None,
+    ),
+    (
+        Kind::DKind(DevelopmentKind::Irrigation),
+        "sprites/development/developments.png",
+        vec2(16., 0.),
+        None,
+    ),
+    (
+        Kind::DKind(DevelopmentKind::Mine),
+        "sprites/development/developments.png",
+        vec2(32., 0.),
+        None,
+    ),
+    (
+        Kind::StKind(SettlementKind::Village),
+        "sprites/settlement/settlements.png",
+        Vec2::ZERO,
+        None,
+    ),
+    (
+        Kind::StKind(SettlementKind::Town),
+        "sprites/settlement/settlements.png",
+        vec2(16., 0.),
+        None,
+    ),
+    (
+        Kind::StKind(SettlementKind::City),
+        "sprites/settlement/settlements.png",
+        vec2(32., 0.),
+        None,
+    ),
+    (
+        Kind::StKind(SettlementKind::Fort),
+        "sprites/settlement/settlements.png",
+        vec2(48., 0.),
+        None,
+    ),
+];
+
+/// Path of an asset, as the asset server would resolve it
+pub fn get_asset_path(path: &str) -> PathBuf {
+    return FileAssetReader::get_base_path().join("assets").join(path);
+}
+
+/// Load the autotile rules of a tileset from the assets folder
+pub fn load_tileset_rules(rules_path: &str) -> Result<AutotileRules, AutotileRulesError> {
+    return AutotileRules::load(get_asset_path(rules_path));
+}